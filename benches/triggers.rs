@@ -0,0 +1,139 @@
+// compares the precompiled TriggerMatcher against checking every trigger one at a time, the way
+// handle_triggers used to. run with `cargo bench`, from the repo root so data/ can be found
+use cynobot::data::{ load_file_rel, load_list_files, ListsMaps, TRIGGERS_FILE };
+use cynobot::matcher::multi_trigger_matches;
+use cynobot::tokenize::{ normalize, tokenize };
+use rand::rngs::StdRng;
+use rand::{ Rng, SeedableRng };
+use std::hint::black_box;
+use std::time::{ Duration, Instant };
+
+const ROUNDS: usize = 20;
+
+type Matches = (Vec<(usize, usize)>, Vec<usize>);
+
+fn fill(word: &str) -> Vec<String> {
+    normalize(&word.replace("{user}", "viewer").replace("{channel}", "channel"))
+}
+
+fn one_by_one(lm: &ListsMaps, text: &str) -> Matches {
+    let words = tokenize(text, &[]);
+    let tokens: Vec<&str> = words.iter().flat_map(|word| word.tokens.iter().map(|token| token.as_str())).collect();
+    let multi = lm.multi_triggers.iter()
+        .enumerate()
+        .filter_map(|(index, multi_trigger)| Some((index, multi_trigger_matches(multi_trigger, &tokens, &fill)?)))
+        .collect();
+    let regex = lm.regex_triggers.iter()
+        .enumerate()
+        .filter(|(_, regex_trigger)| regex_trigger.regex.is_match(text))
+        .map(|(index, _)| index)
+        .collect();
+    (multi, regex)
+}
+
+fn precompiled(lm: &ListsMaps, text: &str) -> Matches {
+    let words = tokenize(text, &[]);
+    let tokens: Vec<&str> = words.iter().flat_map(|word| word.tokens.iter().map(|token| token.as_str())).collect();
+    let multi = lm.matcher.multi_matches(&lm.multi_triggers, &tokens, &fill);
+    let regex = lm.matcher.regex_matches(text);
+    (multi, regex)
+}
+
+fn time(lm: &ListsMaps, messages: &[String], matches: fn(&ListsMaps, &str) -> Matches) -> (Duration, Vec<Matches>) {
+    let results = messages.iter().map(|text| matches(lm, text)).collect();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for text in messages {
+            black_box(matches(lm, black_box(text)));
+        }
+    }
+    (start.elapsed() / (ROUNDS * messages.len()) as u32, results)
+}
+
+fn compare(name: &str, lm: &ListsMaps, messages: &[String]) {
+    let (slow, slow_results) = time(lm, messages, one_by_one);
+    let (fast, fast_results) = time(lm, messages, precompiled);
+    assert!(slow_results == fast_results, "{}: the matchers disagree", name);
+
+    let matched = fast_results.iter().filter(|(multi, regex)| !multi.is_empty() || !regex.is_empty()).count();
+    println!("{}: {} multi word triggers, {} regex triggers, {} of {} messages matched",
+             name, lm.multi_triggers.len(), lm.regex_triggers.len(), matched, messages.len());
+    println!("  one by one   {:>10?} per message", slow);
+    println!("  precompiled  {:>10?} per message ({:.1}x)", fast, slow.as_secs_f64() / fast.as_secs_f64());
+}
+
+fn pick<'a>(rng: &mut StdRng, words: &'a [String]) -> &'a str {
+    &words[rng.gen_range(0..words.len())]
+}
+
+// lots of list expanded looking keys, the case the matcher is for
+fn synthetic(rng: &mut StdRng, vocabulary: &[String]) -> ListsMaps {
+    let mut triggers = String::new();
+    for i in 0..2000 {
+        let length = rng.gen_range(2..5);
+        let words: Vec<&str> = (0..length).map(|_| pick(rng, vocabulary)).collect();
+        let option = match i % 10 {
+            0 => " @ordered",
+            1 => " @near:4",
+            2 => " -nope",
+            3 => " @fuzzy",
+            _ => "",
+        };
+        triggers.push_str(&format!("{}{}=response {}\n", words.join(" "), option, i));
+    }
+    for i in 0..50 {
+        triggers.push_str(&format!("/\\b{}(s|ed)?\\b/=regex {}\n", pick(rng, vocabulary), i));
+    }
+    triggers.push_str("{user} hello=hi {user}\n");
+
+    let lists = vec![
+        (String::from("passive_advice"), String::from("advice\n")),
+        (String::from("questions"), String::from("question?\n")),
+    ];
+    ListsMaps::parse(&lists, &triggers, "", "").unwrap()
+}
+
+// data/ with @fuzzy on every list key, which is what gets slow without the prefilter
+fn fuzzy_data() -> anyhow::Result<ListsMaps> {
+    let triggers: String = load_file_rel(TRIGGERS_FILE)?
+        .lines()
+        .map(|line| match line.split_once('=') {
+            Some((key, value)) if key.starts_with('[') && !key.contains("@fuzzy") => format!("{} @fuzzy={}\n", key, value),
+            _ => format!("{}\n", line),
+        })
+        .collect();
+    ListsMaps::parse(&load_list_files()?, &triggers, "", "")
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let vocabulary: Vec<String> = (0..300).map(|i| format!("word{}", i)).collect();
+    // most of chat doesn't match anything, so half the words aren't in any trigger
+    let chat_words: Vec<String> = vocabulary.iter().cloned().chain((0..300).map(|i| format!("chat{}", i))).collect();
+    let messages: Vec<String> = (0..500)
+        .map(|_| {
+            let length = rng.gen_range(3..16);
+            (0..length).map(|_| pick(&mut rng, &chat_words)).collect::<Vec<_>>().join(" ")
+        })
+        .collect();
+
+    compare("synthetic", &synthetic(&mut rng, &vocabulary), &messages);
+
+    match ListsMaps::load() {
+        Ok(lm) => {
+            let real_words: Vec<String> = lm.lists.values().flat_map(|list| &list.entries).map(|entry| entry.to_lowercase()).collect();
+            let messages: Vec<String> = (0..500)
+                .map(|_| {
+                    let length = rng.gen_range(1..6);
+                    (0..length).map(|_| pick(&mut rng, &real_words)).collect::<Vec<_>>().join(" ")
+                })
+                .collect();
+            compare("data", &lm, &messages);
+            match fuzzy_data() {
+                Ok(fuzzy) => compare("data with @fuzzy", &fuzzy, &messages),
+                Err(e) => println!("skipping data/ with @fuzzy, it failed to load: {:#}", e),
+            }
+        }
+        Err(e) => println!("skipping data/, it failed to load: {:#}", e),
+    }
+}
@@ -0,0 +1,78 @@
+use super::{ ChatBackend, ChatEvent, ChatMessage };
+use std::io::Write;
+use tokio::io::{ AsyncBufReadExt, BufReader, Lines, Stdin };
+
+// reads stdin as if every line was a PRIVMSG sent by `user` in `channel`, and prints whatever the
+// bot sends back. lines starting with / are console commands:
+//   /user <name>     chat as someone else
+//   /channel <name>  chat in another channel
+//   /quit
+pub struct ConsoleBackend {
+    lines: Lines<BufReader<Stdin>>,
+    user: String,
+    channel: String,
+}
+
+impl ConsoleBackend {
+    pub fn new(user: &str, channel: &str) -> Self {
+        ConsoleBackend {
+            lines: BufReader::new(tokio::io::stdin()).lines(),
+            user: String::from(user),
+            channel: String::from(channel),
+        }
+    }
+
+    fn prompt(&self) {
+        print!("[{}] {}> ", self.channel, self.user);
+        let _ = std::io::stdout().flush();
+    }
+}
+
+impl ChatBackend for ConsoleBackend {
+    async fn next_event(&mut self) -> anyhow::Result<ChatEvent> {
+        self.prompt();
+        let line = match self.lines.next_line().await? {
+            Some(line) => line,
+            None => return Ok(ChatEvent::Quit),
+        };
+
+        let mut split = line.split_whitespace();
+        match (split.next(), split.next()) {
+            (Some("/quit"), _) => Ok(ChatEvent::Quit),
+            (Some("/user"), Some(user)) => {
+                self.user = user.to_lowercase();
+                Ok(ChatEvent::Other)
+            }
+            (Some("/channel"), Some(channel)) => {
+                self.channel = channel.trim_start_matches('#').to_lowercase();
+                Ok(ChatEvent::Other)
+            }
+            (None, _) => Ok(ChatEvent::Other),
+            _ => {
+                Ok(ChatEvent::Message(ChatMessage {
+                    channel: self.channel.clone(),
+                    user: self.user.clone(),
+                    text: line,
+                    is_broadcaster: self.user == self.channel,
+                    is_moderator: false,
+                    emotes: Vec::new(),
+                }))
+            }
+        }
+    }
+
+    async fn send_message(&mut self, channel: &str, msg: &str) -> anyhow::Result<()> {
+        println!("[{}] <bot> {}", channel, msg);
+        Ok(())
+    }
+
+    async fn join(&mut self, channel: &str) -> anyhow::Result<()> {
+        println!("joined '{}'!", channel);
+        Ok(())
+    }
+
+    async fn part(&mut self, channel: &str) -> anyhow::Result<()> {
+        println!("left '{}'", channel);
+        Ok(())
+    }
+}
@@ -0,0 +1,330 @@
+use super::{ ChatBackend, ChatEvent, ChatMessage };
+use futures_lite::{ AsyncRead, AsyncWrite };
+use std::collections::{ HashMap, HashSet };
+use twitchchat::{
+    commands, connector::{ self, Connector as _ }, messages::Commands,
+    AsyncDecoder, AsyncEncoder, DecodeError, FromIrcMessage as _, IrcMessage,
+};
+
+type Reader = Box<dyn AsyncRead + Send + Sync + Unpin>;
+type Writer = Box<dyn AsyncWrite + Send + Sync + Unpin>;
+
+// NAMES prefixes and MODE letters for owner, admin, op and halfop, who all count as mods
+const OP_PREFIXES: [char; 4] = ['~', '&', '@', '%'];
+const OP_MODES: [char; 4] = ['q', 'a', 'o', 'h'];
+// the other modes that take a nick or value, which have to be skipped over. +l does too
+const ARG_MODES: [char; 5] = ['v', 'b', 'k', 'e', 'I'];
+
+pub struct IrcConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+    pub nick: String,
+    pub password: Option<String>,
+}
+
+// a plain rfc1459 connection, for running somewhere other than twitch. twitch only extras like
+// tags and capabilities are never requested, so messages arrive without them
+pub struct IrcBackend {
+    decoder: AsyncDecoder<Reader>,
+    encoder: AsyncEncoder<Writer>,
+    ops: Ops,
+}
+
+// who has op in each channel. anyone can take a nick on plain irc, so mods are whoever the server
+// says has op, from NAMES replies and MODE changes, rather than guessed from names
+#[derive(Default)]
+struct Ops {
+    channels: HashMap<String, HashSet<String>>,
+}
+
+fn nick(msg: &IrcMessage<'_>) -> Option<String> {
+    let prefix = msg.get_prefix()?.trim_start_matches(':');
+    Some(prefix.split('!').next()?.to_lowercase())
+}
+
+fn channel_name(channel: &str) -> String {
+    channel.trim_start_matches('#').to_lowercase()
+}
+
+impl Ops {
+    fn is_op(&self, channel: &str, nick: &str) -> bool {
+        self.channels.get(&channel_name(channel)).is_some_and(|ops| ops.contains(&nick.to_lowercase()))
+    }
+
+    fn set(&mut self, channel: &str, nick: &str, op: bool) {
+        let ops = self.channels.entry(channel_name(channel)).or_default();
+        if op {
+            ops.insert(nick.to_lowercase());
+        } else {
+            ops.remove(&nick.to_lowercase());
+        }
+    }
+
+    fn update(&mut self, msg: &IrcMessage<'_>) {
+        match msg.get_command() {
+            // "bot = #channel :@alice +bob carol"
+            "353" => {
+                let (Some(channel), Some(names)) = (msg.nth_arg(2), msg.get_data()) else { return };
+                for name in names.split_whitespace() {
+                    let op = name.starts_with(OP_PREFIXES);
+                    self.set(channel, name.trim_start_matches(|c: char| "~&@%+".contains(c)), op);
+                }
+            }
+            // "#channel +o-v alice bob"
+            "MODE" => {
+                let Some(channel) = msg.nth_arg(0).filter(|channel| channel.starts_with('#')) else { return };
+                let Some(modes) = msg.nth_arg(1) else { return };
+                let mut args = (2..).map_while(|nth| msg.nth_arg(nth)).chain(msg.get_data());
+                let mut adding = true;
+                for mode in modes.chars() {
+                    match mode {
+                        '+' => adding = true,
+                        '-' => adding = false,
+                        _ if OP_MODES.contains(&mode) => {
+                            if let Some(nick) = args.next() {
+                                self.set(channel, nick, adding);
+                            }
+                        }
+                        _ if ARG_MODES.contains(&mode) || (mode == 'l' && adding) => {
+                            args.next();
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            "PART" => {
+                if let (Some(channel), Some(nick)) = (msg.nth_arg(0).or(msg.get_data()), nick(msg)) {
+                    self.set(channel, &nick, false);
+                }
+            }
+            "KICK" => {
+                if let (Some(channel), Some(nick)) = (msg.nth_arg(0), msg.nth_arg(1)) {
+                    self.set(channel, nick, false);
+                }
+            }
+            "QUIT" => {
+                if let Some(nick) = nick(msg) {
+                    for ops in self.channels.values_mut() {
+                        ops.remove(&nick);
+                    }
+                }
+            }
+            "NICK" => {
+                if let (Some(old), Some(new)) = (nick(msg), msg.get_data().or(msg.nth_arg(0))) {
+                    for ops in self.channels.values_mut() {
+                        if ops.remove(&old) {
+                            ops.insert(new.to_lowercase());
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn split<T>(stream: T) -> (Reader, Writer)
+where
+    T: Send + Sync + 'static,
+    for<'a> &'a T: AsyncRead + AsyncWrite + Send + Sync + Unpin,
+{
+    let read = async_dup::Arc::new(stream);
+    let write = read.clone();
+    (Box::new(read), Box::new(write))
+}
+
+// twitchchat's parsers expect twitch's flavour of irc, and fail on valid messages from other
+// servers, like a JOIN with the channel as trailing data. those are skipped, not fatal
+fn parse(msg: IrcMessage<'_>) -> Option<Commands<'_>> {
+    let raw = msg.get_raw().trim_end().to_string();
+    match Commands::from_irc(msg) {
+        Ok(command) => Some(command),
+        Err(e) => {
+            println!("skipping unparsed message '{}': {}", raw, e);
+            None
+        }
+    }
+}
+
+impl IrcBackend {
+    pub async fn connect(config: &IrcConfig) -> anyhow::Result<Self> {
+        let addr = (config.host.as_str(), config.port);
+
+        println!("Connecting to {}:{}...", config.host, config.port);
+        let (read, write) = if config.tls {
+            split(connector::tokio::ConnectorRustTls::custom(addr, &config.host)?.connect().await?)
+        } else {
+            split(connector::tokio::Connector::custom(addr)?.connect().await?)
+        };
+
+        let mut backend = IrcBackend {
+            decoder: AsyncDecoder::new(read),
+            encoder: AsyncEncoder::new(write),
+            ops: Ops::default(),
+        };
+        backend.register(config).await?;
+        println!("..Connected");
+
+        Ok(backend)
+    }
+
+    async fn register(&mut self, config: &IrcConfig) -> anyhow::Result<()> {
+        if let Some(password) = &config.password {
+            self.encoder.encode(commands::raw(&format!("PASS {}", password))).await?;
+        }
+        self.encoder.encode(commands::raw(&format!("NICK {}", config.nick))).await?;
+        self.encoder.encode(commands::raw(&format!("USER {} 0 * :{}", config.nick, config.nick))).await?;
+
+        // the server is ready for us once it sends the 001 welcome
+        loop {
+            let msg = self.decoder.read_message().await?;
+            match msg.get_command() {
+                "433" => anyhow::bail!("nick {} is already in use", config.nick),
+                "ERROR" => anyhow::bail!("server refused the connection: {}", msg.get_data().unwrap_or("")),
+                // 432 bad nick, 464 bad password, 465 banned... none of them are followed by a 001
+                command if is_error_numeric(command) => anyhow::bail!(
+                    "server refused the connection: {} {}",
+                    command,
+                    msg.get_data().unwrap_or("")
+                ),
+                _ => {}
+            }
+            match parse(msg) {
+                Some(Commands::IrcReady(_)) => return Ok(()),
+                Some(Commands::Ping(ping)) => {
+                    let token = ping.token().to_string();
+                    self.encoder.encode(commands::pong(&token)).await?;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn is_error_numeric(command: &str) -> bool {
+    command.len() == 3 && command.starts_with(['4', '5']) && command.bytes().all(|b| b.is_ascii_digit())
+}
+
+impl ChatBackend for IrcBackend {
+    async fn next_event(&mut self) -> anyhow::Result<ChatEvent> {
+        let msg = match self.decoder.read_message().await {
+            Ok(msg) => msg,
+            Err(DecodeError::Eof) => {
+                println!("Eof");
+                return Ok(ChatEvent::Quit);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        self.ops.update(&msg);
+        match parse(msg) {
+            Some(Commands::Privmsg(msg)) => {
+                // plain irc has no broadcaster, channel ops are its mods. names are lowercased, a
+                // server can send back #CynoKron for the #cynokron that was joined
+                Ok(ChatEvent::Message(ChatMessage {
+                    is_broadcaster: false,
+                    is_moderator: self.ops.is_op(msg.channel(), msg.name()),
+                    channel: channel_name(msg.channel()),
+                    user: msg.name().to_lowercase(),
+                    text: msg.data().to_string(),
+                    emotes: Vec::new(),
+                }))
+            }
+            Some(Commands::Ping(ping)) => {
+                let token = ping.token().to_string();
+                self.encoder.encode(commands::pong(&token)).await?;
+                Ok(ChatEvent::Other)
+            }
+            _ => Ok(ChatEvent::Other),
+        }
+    }
+
+    async fn send_message(&mut self, channel: &str, msg: &str) -> anyhow::Result<()> {
+        self.encoder.encode(commands::privmsg(channel, msg)).await?;
+        Ok(())
+    }
+
+    async fn join(&mut self, channel: &str) -> anyhow::Result<()> {
+        self.encoder.encode(commands::join(channel)).await?;
+        Ok(())
+    }
+
+    async fn part(&mut self, channel: &str) -> anyhow::Result<()> {
+        self.encoder.encode(commands::part(channel)).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ops(lines: &[&str]) -> Ops {
+        let mut ops = Ops::default();
+        for line in lines {
+            let raw = format!("{}\r\n", line);
+            let (_, msg) = twitchchat::irc::parse_one(&raw).unwrap();
+            ops.update(&msg);
+        }
+        ops
+    }
+
+    #[test]
+    fn error_numerics() {
+        for command in ["432", "433", "464", "465", "502"] {
+            assert!(is_error_numeric(command), "{}", command);
+        }
+        for command in ["001", "353", "372", "4", "4a5", "4000", "PING", "ERROR"] {
+            assert!(!is_error_numeric(command), "{}", command);
+        }
+    }
+
+    #[test]
+    fn names() {
+        let ops = ops(&[":srv 353 bot = #Chan :bot ~owner &admin @Alice %half +voiced plain"]);
+        for nick in ["owner", "admin", "alice", "ALICE", "half"] {
+            assert!(ops.is_op("#chan", nick), "{}", nick);
+            assert!(ops.is_op("chan", nick), "{}", nick);
+        }
+        for nick in ["bot", "voiced", "plain", "nobody"] {
+            assert!(!ops.is_op("#chan", nick), "{}", nick);
+        }
+        assert!(!ops.is_op("#other", "alice"));
+    }
+
+    #[test]
+    fn modes() {
+        let ops = ops(&[
+            ":srv 353 bot = #chan :@alice bob carol",
+            // the ban mask and the key take an argument too, they mustn't shift which nick is which
+            ":alice!u@h MODE #chan +bo-o+k *!*@spam bob alice secret",
+            ":alice!u@h MODE #chan +lv 10 carol",
+            ":bob!u@h MODE #chan +h carol",
+            ":srv MODE bot +i",
+        ]);
+        assert!(ops.is_op("#chan", "bob"));
+        assert!(!ops.is_op("#chan", "alice"));
+        assert!(ops.is_op("#chan", "carol"));
+        assert!(!ops.is_op("#chan", "*!*@spam"));
+    }
+
+    #[test]
+    fn leaving_and_renaming() {
+        let names = ":srv 353 bot = #chan :@alice @bob @carol @dave";
+        let ops = ops(&[
+            names,
+            ":srv 353 bot = #other :@alice",
+            ":alice!u@h PART #chan :bye",
+            ":carol!u@h KICK #chan bob :out",
+            ":carol!u@h NICK :caroline",
+            ":dave!u@h QUIT :gone",
+        ]);
+        assert!(!ops.is_op("#chan", "alice"));
+        assert!(ops.is_op("#other", "alice"));
+        assert!(!ops.is_op("#chan", "bob"));
+        assert!(!ops.is_op("#chan", "carol"));
+        assert!(ops.is_op("#chan", "caroline"));
+        assert!(!ops.is_op("#chan", "dave"));
+    }
+}
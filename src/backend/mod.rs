@@ -0,0 +1,48 @@
+mod console;
+mod irc;
+mod replay;
+mod twitch;
+
+pub use console::ConsoleBackend;
+pub use irc::{ IrcBackend, IrcConfig };
+pub use replay::ReplayBackend;
+pub use twitch::TwitchBackend;
+
+use std::ops::Range;
+
+// a chat message, stripped of anything backend specific
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+    pub channel: String,
+    pub user: String,
+    pub text: String,
+    pub is_broadcaster: bool,
+    pub is_moderator: bool,
+    // where emotes are in text, in chars. only twitch tells us
+    pub emotes: Vec<Range<usize>>,
+}
+
+impl ChatMessage {
+    // allowed to use commands that change how the bot behaves
+    pub fn is_privileged(&self) -> bool {
+        self.is_broadcaster || self.is_moderator
+    }
+}
+
+#[derive(Debug)]
+pub enum ChatEvent {
+    Message(ChatMessage),
+    // something happened on the connection that the bot doesn't care about
+    Other,
+    Quit,
+}
+
+// everything the bot needs from a chat connection. channel names never include the leading #
+// the bot runs on a single task, so the futures never need to be Send
+#[allow(async_fn_in_trait)]
+pub trait ChatBackend {
+    async fn next_event(&mut self) -> anyhow::Result<ChatEvent>;
+    async fn send_message(&mut self, channel: &str, msg: &str) -> anyhow::Result<()>;
+    async fn join(&mut self, channel: &str) -> anyhow::Result<()>;
+    async fn part(&mut self, channel: &str) -> anyhow::Result<()>;
+}
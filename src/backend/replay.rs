@@ -0,0 +1,130 @@
+use super::{ ChatBackend, ChatEvent, ChatMessage };
+use crate::env::{ Clock as _, ManualClock };
+use anyhow::Context as _;
+use std::fs::File;
+use std::io::{ BufWriter, Write };
+use std::path::Path;
+use std::time::{ Duration, SystemTime, UNIX_EPOCH };
+
+// feeds a recorded chat log through the bot and writes everything it says to a transcript.
+// each log line is "<unix seconds>\t<channel>\t<user>\t<text>", lines starting with - are comments.
+// the clock is moved to each message's timestamp before the bot sees it
+pub struct ReplayBackend {
+    lines: std::vec::IntoIter<(SystemTime, ChatMessage)>,
+    transcript: BufWriter<File>,
+    clock: ManualClock,
+}
+
+fn parse_timestamp(stamp: &str) -> Option<SystemTime> {
+    // "inf" and "1e30" parse as floats too, they just don't fit in a time
+    let secs = Duration::try_from_secs_f64(stamp.parse().ok()?).ok()?;
+    UNIX_EPOCH.checked_add(secs)
+}
+
+fn format_timestamp(stamp: SystemTime) -> String {
+    let since_epoch = stamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("{}.{:03}", since_epoch.as_secs(), since_epoch.subsec_millis())
+}
+
+fn parse_log(contents: &str) -> anyhow::Result<Vec<(SystemTime, ChatMessage)>> {
+    let mut lines = Vec::new();
+    for (line_number, line) in contents.lines().enumerate() {
+        if line.is_empty() { continue; }
+        if let Some('-') = line.chars().next() {
+            continue;
+        }
+
+        let mut split = line.splitn(4, '\t');
+        let parsed = match (split.next().and_then(parse_timestamp), split.next(), split.next(), split.next()) {
+            (Some(stamp), Some(channel), Some(user), Some(text)) => {
+                let channel = channel.trim_start_matches('#').to_lowercase();
+                let user = user.to_lowercase();
+                (stamp, ChatMessage {
+                    is_broadcaster: user == channel,
+                    is_moderator: false,
+                    channel,
+                    user,
+                    text: String::from(text),
+                    emotes: Vec::new(),
+                })
+            }
+            _ => anyhow::bail!("line {}: expected \"<unix seconds>\\t<channel>\\t<user>\\t<text>\"", line_number + 1),
+        };
+        lines.push(parsed);
+    }
+    Ok(lines)
+}
+
+impl ReplayBackend {
+    pub fn open(log: &Path, transcript: &Path, clock: ManualClock) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(log).with_context(|| format!("failed to read {:?}", log))?;
+        let lines = parse_log(&contents).with_context(|| format!("failed to parse {:?}", log))?;
+        let transcript = File::create(transcript).with_context(|| format!("failed to create {:?}", transcript))?;
+
+        Ok(ReplayBackend {
+            lines: lines.into_iter(),
+            transcript: BufWriter::new(transcript),
+            clock,
+        })
+    }
+}
+
+impl ChatBackend for ReplayBackend {
+    async fn next_event(&mut self) -> anyhow::Result<ChatEvent> {
+        match self.lines.next() {
+            Some((stamp, msg)) => {
+                self.clock.set(stamp);
+                Ok(ChatEvent::Message(msg))
+            }
+            None => {
+                self.transcript.flush()?;
+                Ok(ChatEvent::Quit)
+            }
+        }
+    }
+
+    async fn send_message(&mut self, channel: &str, msg: &str) -> anyhow::Result<()> {
+        writeln!(self.transcript, "{}\t{}\t{}", format_timestamp(self.clock.now()), channel, msg)?;
+        Ok(())
+    }
+
+    async fn join(&mut self, _channel: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn part(&mut self, _channel: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(contents: &str) -> String {
+        format!("{:#}", parse_log(contents).unwrap_err())
+    }
+
+    #[test]
+    fn parses_lines() {
+        let lines = parse_log("- comment\n\n1700000000.5\t#Chan\tChan\thi\tthere\n1700000001\tchan\tbob\t\n").unwrap();
+        assert_eq!(lines.len(), 2);
+        let (stamp, msg) = &lines[0];
+        assert_eq!(format_timestamp(*stamp), "1700000000.500");
+        assert_eq!((msg.channel.as_str(), msg.user.as_str(), msg.text.as_str()), ("chan", "chan", "hi\tthere"));
+        assert!(msg.is_broadcaster);
+        assert!(!lines[1].1.is_broadcaster);
+        assert_eq!(lines[1].1.text, "");
+    }
+
+    #[test]
+    fn bad_lines_give_their_line_number() {
+        // comments and blank lines still count
+        assert!(error("- comment\n\n1700000000\tchan\tbob\n").starts_with("line 3: "));
+        assert!(error("1700000000\tchan\tbob\thi\nnope\tchan\tbob\thi\n").starts_with("line 2: "));
+        assert!(error("inf\tchan\tbob\thi\n").starts_with("line 1: "));
+        assert!(error("1e30\tchan\tbob\thi\n").starts_with("line 1: "));
+        assert!(error("NaN\tchan\tbob\thi\n").starts_with("line 1: "));
+        assert!(error("1700000000 chan bob hi\n").starts_with("line 1: "));
+    }
+}
@@ -0,0 +1,69 @@
+use super::{ ChatBackend, ChatEvent, ChatMessage };
+use twitchchat::{
+    commands, connector, messages,
+    runner::{AsyncRunner, Status},
+    UserConfig,
+};
+
+pub struct TwitchBackend {
+    runner: AsyncRunner,
+}
+
+impl TwitchBackend {
+    pub async fn connect(user_config: &UserConfig) -> anyhow::Result<Self> {
+        let connector = connector::tokio::ConnectorRustTls::twitch()?;
+
+        println!("Connecting...");
+        let runner = AsyncRunner::connect(connector, user_config).await?;
+        println!("..Connected");
+
+        Ok(TwitchBackend { runner })
+    }
+}
+
+impl ChatBackend for TwitchBackend {
+    async fn next_event(&mut self) -> anyhow::Result<ChatEvent> {
+        use messages::Commands::*;
+        match self.runner.next_message().await? {
+            Status::Message(Privmsg(msg)) => {
+                Ok(ChatEvent::Message(ChatMessage {
+                    channel: msg.channel()[1..].to_string(), // strip the #
+                    user: msg.name().to_string(),
+                    text: msg.data().to_string(),
+                    is_broadcaster: msg.is_broadcaster(),
+                    is_moderator: msg.is_moderator(),
+                    // twitch's ranges include the last char
+                    emotes: msg.emotes().iter()
+                        .flat_map(|emote| emote.ranges.iter())
+                        .map(|range| range.start as usize..range.end as usize + 1)
+                        .collect(),
+                }))
+            }
+            Status::Message(_) => Ok(ChatEvent::Other),
+            Status::Quit => {
+                println!("Quitting.");
+                Ok(ChatEvent::Quit)
+            }
+            Status::Eof => {
+                println!("Eof");
+                Ok(ChatEvent::Quit)
+            }
+        }
+    }
+
+    async fn send_message(&mut self, channel: &str, msg: &str) -> anyhow::Result<()> {
+        let mut writer = self.runner.writer();
+        writer.encode(commands::privmsg(channel, msg)).await?;
+        Ok(())
+    }
+
+    async fn join(&mut self, channel: &str) -> anyhow::Result<()> {
+        self.runner.join(channel).await?;
+        Ok(())
+    }
+
+    async fn part(&mut self, channel: &str) -> anyhow::Result<()> {
+        self.runner.part(channel).await?;
+        Ok(())
+    }
+}
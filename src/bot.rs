@@ -0,0 +1,141 @@
+use crate::backend::{ ChatBackend, ChatEvent, ChatMessage };
+use crate::commands::parse_command;
+use crate::data::ListsMaps;
+use crate::env::Env;
+use crate::reload::{ DataWatcher, RELOAD_POLL_INTERVAL };
+use crate::state::{ ChannelState, MetaState, Mood, State, PASSIVE_ADVICE_INTERVAL };
+use crate::template::{ pick_from, substitute_random };
+use std::error::Error;
+
+pub const PASSIVE_MESSAGES: bool = true;
+
+pub async fn run(meta_state: MetaState, env: Env, state: State, backend: impl ChatBackend) -> Result<(), Box<dyn Error>> {
+    let lm = ListsMaps::load()?;
+    println!("loaded {}", lm.summary());
+
+    println!("starting main loop"); 
+    main_loop(meta_state, env, state, lm, backend).await 
+}
+
+pub async fn send_passive_advice(state: &mut ChannelState, lm: &ListsMaps, env: &mut Env, backend: &mut impl ChatBackend, force: bool) {
+    let msg = match pick_from(lm, &mut env.rng, &mut state.list_history, "passive_advice") {
+        Some(msg) => msg,
+        None => return,
+    };
+    let result = substitute_random(lm, &mut env.rng, &mut state.list_history, &msg); 
+    if force {
+        state.force_send_message(env, backend, &result).await
+    } else {
+        state.send_message(env, backend, &result).await;
+    }
+}
+
+pub async fn send_passive_question(state: &mut ChannelState, lm: &ListsMaps, env: &mut Env, backend: &mut impl ChatBackend, force: bool) {
+    let msg = match pick_from(lm, &mut env.rng, &mut state.list_history, "questions") {
+        Some(msg) => msg,
+        None => return,
+    };
+    let result = substitute_random(lm, &mut env.rng, &mut state.list_history, &msg); 
+    if force {
+        state.force_send_message(env, backend, &result).await
+    } else {
+        state.send_message(env, backend, &result).await;
+    }
+}
+
+pub async fn main_loop(mut meta_state: MetaState, mut env: Env, mut state: State, mut lm: ListsMaps, mut backend: impl ChatBackend) -> Result<(), Box<dyn Error>> {
+    let mut watcher = if meta_state.hot_reload { Some(DataWatcher::new(&env)?) } else { None };
+    loop {
+
+        if meta_state.persist {
+            let _ = MetaState::clean_temp_files();
+        }
+        if let Err(e) = meta_state.try_write_state(&mut env, &state) {
+            println!("failed to write state {:?}", e);
+        }
+
+        // a quiet channel can go a long time between events, so the data is also checked on a
+        // timer. the same next_event is kept waiting, dropping it could lose a half read line
+        let event = {
+            let next_event = backend.next_event();
+            tokio::pin!(next_event);
+            loop {
+                tokio::select! {
+                    event = &mut next_event => break event?,
+                    _ = tokio::time::delay_for(RELOAD_POLL_INTERVAL), if watcher.is_some() => {
+                        poll_data(&mut watcher, &env, &mut lm);
+                    }
+                }
+            }
+        };
+
+        // check for edits before handling the event, so it already sees them
+        poll_data(&mut watcher, &env, &mut lm);
+
+        match event {
+            ChatEvent::Message(msg) => {
+                handle_message(&mut state, &mut lm, &mut env, &mut backend, msg).await;
+            }
+            ChatEvent::Other => {}
+            ChatEvent::Quit => {
+                break;
+            }
+        }
+
+        // go through channels in a fixed order so replays come out the same every time
+        let mut channels: Vec<&mut ChannelState> = state.channels.values_mut().collect();
+        channels.sort_by(|a, b| a.channel_name.cmp(&b.channel_name));
+        for cstate in channels {
+            if cstate.advice_due(&env) {
+                match cstate.mood {
+                    Mood::Normal => {
+                        if PASSIVE_MESSAGES && !cstate.dedup_message { 
+                            send_passive_advice(cstate, &lm, &mut env, &mut backend, false).await;
+                        }
+
+                    }
+                    Mood::Backoff => {
+                        cstate.set_mood(Mood::Normal);
+                        cstate.next_advice = PASSIVE_ADVICE_INTERVAL;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn poll_data(watcher: &mut Option<DataWatcher>, env: &Env, lm: &mut ListsMaps) {
+    if let Some(watcher) = watcher {
+        if let Some(changed) = watcher.poll(env) {
+            reload(lm, &changed);
+        }
+    }
+}
+
+// swap in freshly loaded data, or keep the old data if the new files are broken
+pub fn reload(lm: &mut ListsMaps, changed: &[String]) {
+    println!("detected changes in {}", changed.join(", "));
+    match ListsMaps::load() {
+        Ok(new_lm) => {
+            println!("reloaded, was {}, now {}", lm.summary(), new_lm.summary());
+            *lm = new_lm;
+        }
+        Err(e) => {
+            println!("failed to reload, keeping the old data: {:#}", e);
+        }
+    }
+}
+
+pub async fn handle_message(state: &mut State, lm: &mut ListsMaps, env: &mut Env, backend: &mut impl ChatBackend, msg: ChatMessage) {
+    println!("[{}] {}: {}", msg.channel, msg.user, msg.text);
+    let cstate = state.channels.entry(State::chash(&msg.channel)).or_insert_with(|| ChannelState::new(&msg.channel, env));
+    if state.ignore_cooldowns {
+        cstate.direct_message = true;
+    }
+    parse_command(state, lm, env, backend, &msg).await.unwrap();
+    if let Some( cstate ) = state.channels.get_mut(&State::chash(&msg.channel)) {
+        cstate.dedup_message = false;
+    }
+}
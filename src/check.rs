@@ -0,0 +1,487 @@
+use crate::data::{
+    compile_regex, config_dir, data_dir, list_option, parse_key, regex_key, split_line, split_weight, weight_prefix, MatchMode, Repeat, COMMANDS_TEXT_FILE, CONFIG_CHANNELS, CONFIG_COMMANDS, REQUIRED_LISTS, TRIGGERS_FILE,
+};
+use crate::template::{ placeholders, subst_global, FILTERS, MAX_DEPTH };
+use crate::tokenize::{ normalize, normalize_key };
+use std::borrow::Cow;
+use std::collections::{ HashMap, HashSet };
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// placeholders filled in from the message instead of from a list
+pub const CONTEXT_PLACEHOLDERS: [&str; 4] = ["trigger", "user", "channel", "me"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        if self.line == 0 {
+            write!(f, "{}: {}: {}", self.file, severity, self.message)
+        } else {
+            write!(f, "{}:{}: {}: {}", self.file, self.line, severity, self.message)
+        }
+    }
+}
+
+struct Checker<'a> {
+    lists: &'a HashMap<String, Vec<(usize, String)>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// the same lines parse_list keeps, with their line numbers
+fn list_lines(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    contents.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('-'))
+        .map(|(line_number, line)| (line_number + 1, line))
+}
+
+// list_lines without the @option lines, the entries List::parse keeps
+fn list_entries(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    list_lines(contents).filter(|(_, line)| list_option(line).is_none())
+}
+
+impl<'a> Checker<'a> {
+    fn report(&mut self, severity: Severity, file: &str, line: usize, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            file: String::from(file),
+            line,
+            message,
+        });
+    }
+
+    // captures is the number of groups of the regex the text responds to, None when it isn't
+    // known, like for list entries
+    fn check_placeholders(&mut self, file: &str, line: usize, text: &str, captures: Option<usize>) {
+        // {foods:a} only makes {a} usable after it
+        let mut bound = Vec::new();
+        for placeholder in placeholders(text) {
+            let name = placeholder.name;
+            if let Ok(group) = name.parse::<usize>() {
+                match captures {
+                    Some(0) => self.report(Severity::Error, file, line,
+                                           format!("capture placeholder '{{{}}}' without a regex key", name)),
+                    Some(captures) if group >= captures => self.report(Severity::Error, file, line,
+                                           format!("capture placeholder '{{{}}}' but the regex only has {} groups", name, captures - 1)),
+                    _ => {}
+                }
+            } else if !self.lists.contains_key(name) && !CONTEXT_PLACEHOLDERS.contains(&name) && !bound.contains(&name) {
+                self.report(Severity::Error, file, line, format!("unknown placeholder '{{{}}}'", placeholder.raw));
+            }
+
+            for filter in &placeholder.filters {
+                if !FILTERS.contains(filter) {
+                    self.report(Severity::Error, file, line,
+                                format!("unknown filter '{}' in '{{{}}}', expected one of {}", filter, placeholder.raw, FILTERS.join(", ")));
+                }
+            }
+
+            if let Some(binding) = placeholder.binding {
+                if binding.is_empty() {
+                    self.report(Severity::Error, file, line, format!("empty binding name in '{{{}}}'", placeholder.raw));
+                } else if CONTEXT_PLACEHOLDERS.contains(&binding) || binding.parse::<usize>().is_ok() {
+                    self.report(Severity::Error, file, line,
+                                format!("binding '{}' in '{{{}}}' can never be used, {{{}}} means something else", binding, placeholder.raw, binding));
+                } else if self.lists.contains_key(binding) {
+                    self.report(Severity::Warning, file, line,
+                                format!("binding '{}' in '{{{}}}' hides the list of the same name", binding, placeholder.raw));
+                }
+                bound.push(binding);
+            }
+        }
+    }
+
+    // follows the lists a list's entries use, looking for a way back to the start. path is the way
+    // there so far
+    fn find_cycle(&self, start: &str, path: &mut Vec<&'a str>, visited: &mut HashSet<&'a str>) -> bool {
+        let lists = self.lists;
+        let current = *path.last().unwrap();
+        for (_, entry) in &lists[current] {
+            for placeholder in placeholders(entry) {
+                let name = placeholder.name;
+                if name == start {
+                    return true;
+                }
+                if let Some((name, _)) = lists.get_key_value(name) {
+                    if visited.insert(name) {
+                        path.push(name);
+                        if self.find_cycle(start, path, visited) {
+                            return true;
+                        }
+                        path.pop();
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    // the longest chain of lists an entry of this one can pull in
+    fn depth(&self, name: &str, visiting: &mut Vec<String>) -> usize {
+        if visiting.iter().any(|list| list == name) {
+            return 0;
+        }
+        visiting.push(String::from(name));
+        let depth = self.lists[name].iter()
+            .flat_map(|(_, entry)| placeholders(entry))
+            .filter(|placeholder| self.lists.contains_key(placeholder.name))
+            .map(|placeholder| self.depth(placeholder.name, visiting) + 1)
+            .max()
+            .unwrap_or(0);
+        visiting.pop();
+        depth
+    }
+
+    fn check_options(&mut self, file: &str, contents: &str) {
+        let mut first = None;
+        let mut last = None;
+        for (line, text) in list_lines(contents) {
+            match list_option(text) {
+                Some(option) => {
+                    match first {
+                        Some(first) => self.report(Severity::Warning, file, line,
+                                                   format!("list already has an option on line {}, this one replaces it", first)),
+                        None => first = Some(line),
+                    }
+                    last = Some((line, option));
+                }
+                // a typo would quietly turn the option into something the bot says
+                None if text.starts_with('@') && text.len() > 1 && !text.contains(char::is_whitespace) && !text.contains('{') => {
+                    self.report(Severity::Warning, file, line,
+                                format!("'{}' isn't a list option, expected @shuffle or @avoid:N, it will be used as an entry", text));
+                }
+                None => {}
+            }
+        }
+        if let Some((line, Repeat::Shuffle)) = last {
+            if list_entries(contents).any(|(_, entry)| split_weight(entry).0 > 1) {
+                self.report(Severity::Warning, file, line,
+                            String::from("@shuffle picks each entry once a round, weights above 1 only change the order"));
+            }
+        }
+    }
+
+    fn check_weights(&mut self, file: &str, contents: &str) {
+        let (mut entries, mut off) = (0, 0);
+        for (line, entry) in list_entries(contents) {
+            entries += 1;
+            match weight_prefix(entry).map(|(weight, _)| (weight, weight.parse::<u32>())) {
+                Some((_, Ok(0))) => off += 1,
+                Some((weight, Err(_))) => self.report(Severity::Error, file, line,
+                                                      format!("weight {} is too big, the most is {}", weight, u32::MAX)),
+                _ => {}
+            }
+        }
+        if entries > 0 && off == entries {
+            self.report(Severity::Error, file, 0, String::from("every entry has a weight of 0, nothing can be picked"));
+        }
+    }
+
+    fn check_list(&mut self, name: &str, file: &str, entries: &[(usize, String)]) {
+        if entries.is_empty() {
+            self.report(Severity::Error, file, 0, String::from("list is empty"));
+        }
+        for (line, entry) in entries {
+            self.check_placeholders(file, *line, entry, None);
+        }
+
+        if let Some((name, _)) = self.lists.get_key_value(name) {
+            let mut path = vec![&name[..]];
+            if self.find_cycle(name, &mut path, &mut HashSet::new()) {
+                self.report(Severity::Warning, file, 0,
+                            format!("list includes itself through {} -> {}, it won't be expanded the second time", path.join(" -> "), name));
+                return;
+            }
+        }
+        // a list used as a value is itself one level
+        if self.depth(name, &mut Vec::new()) + 1 > MAX_DEPTH {
+            self.report(Severity::Warning, file, 0,
+                        format!("lists can be nested more than {} deep from here, the deepest ones won't be expanded", MAX_DEPTH));
+        }
+    }
+
+    // mirrors load_map, but keeps going after a problem so everything is reported at once
+    fn check_map(&mut self, file: &str, contents: &str, normalize_keys: bool) {
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for (line_number, line) in contents.lines().enumerate() {
+            let line_number = line_number + 1;
+            if line.is_empty() || line.starts_with('-') { continue; }
+
+            let (meta_key, value) = match split_line(line) {
+                Some(split) => split,
+                None => {
+                    self.report(Severity::Error, file, line_number, String::from("expected key=value"));
+                    continue;
+                }
+            };
+            if meta_key.is_empty() || value.is_empty() {
+                self.report(Severity::Error, file, line_number, String::from("empty key or value"));
+                continue;
+            }
+            let (meta_key, options) = match parse_key(meta_key) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    self.report(Severity::Error, file, line_number, format!("{:#}", e));
+                    continue;
+                }
+            };
+
+            let mut captures = Some(0);
+            if let Some(pattern) = regex_key(meta_key) {
+                match compile_regex(pattern) {
+                    Ok(regex) => captures = Some(regex.captures_len()),
+                    Err(e) => {
+                        self.report(Severity::Error, file, line_number, format!("invalid regex: {}", e));
+                        captures = None;
+                    }
+                }
+            }
+
+            if let Some(name) = value.strip_prefix('[') {
+                if !self.lists.contains_key(name) {
+                    self.report(Severity::Error, file, line_number, format!("unknown list '{}'", name));
+                }
+            } else {
+                self.check_placeholders(file, line_number, value, captures);
+            }
+
+            // regexes can't be compared against the other keys
+            if regex_key(meta_key).is_some() { continue; }
+
+            let keys: Vec<String> = if let Some(name) = meta_key.strip_prefix('[') {
+                match self.lists.get(name) {
+                    Some(entries) => entries.iter().map(|(_, entry)| entry.clone()).collect(),
+                    None => {
+                        self.report(Severity::Error, file, line_number, format!("unknown list '{}'", name));
+                        continue;
+                    }
+                }
+            } else {
+                vec![String::from(meta_key)]
+            };
+
+            for key in keys {
+                if key.contains(' ') && key.split_whitespace().all(|word| word.len() > 1 && word.starts_with('-')) {
+                    self.report(Severity::Warning, file, line_number,
+                                format!("key '{}' only has negative words and will never match", key));
+                }
+                if let MatchMode::Near(distance) = options.match_mode {
+                    // repeated words only need to be there once, see words_match
+                    let words: HashSet<String> = key.split_whitespace()
+                        .filter(|word| !(word.len() > 1 && word.starts_with('-')))
+                        .flat_map(|word| if word.contains('{') { vec![String::from(word)] } else { normalize(word) })
+                        .collect();
+                    if words.len() > distance {
+                        self.report(Severity::Warning, file, line_number,
+                                    format!("key '{}' has {} words, they never fit within @near:{} and it will never match", key, words.len(), distance));
+                    }
+                }
+                if !key.contains(' ') && subst_global(Cow::Borrowed(&key)).contains('{') {
+                    self.report(Severity::Warning, file, line_number,
+                                format!("single word key '{}' can only use {{me}} and will never match", key));
+                }
+                // compare keys the way they're stored
+                let key = match (normalize_keys, key.contains(' ')) {
+                    (false, _) => key,
+                    (true, false) => normalize_key(&subst_global(Cow::Borrowed(&key))),
+                    (true, true) => subst_global(Cow::Borrowed(&key)).to_lowercase(),
+                };
+                if let Some(first) = seen.get(&key) {
+                    self.report(Severity::Warning, file, line_number,
+                                format!("duplicate key '{}', first defined on line {}", key, first));
+                } else {
+                    seen.insert(key, line_number);
+                }
+            }
+        }
+    }
+}
+
+fn read(path: &Path, name: &str, diagnostics: &mut Vec<Diagnostic>) -> Option<String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Some(contents),
+        Err(e) => {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                file: String::from(name),
+                line: 0,
+                message: format!("failed to read: {}", e),
+            });
+            None
+        }
+    }
+}
+
+// validate every list and map the bot loads, without stopping at the first problem
+pub fn check() -> anyhow::Result<Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+
+    let mut list_files = Vec::new();
+    for file in fs::read_dir(data_dir()?)?.filter_map(|file| file.ok()) {
+        let path = file.path();
+        if path.extension().is_none_or(|ext| ext != "list") { continue; }
+        if let (Some(stem), Some(contents)) = (path.file_stem(), read(&path, &format!("data/{}", file.file_name().to_string_lossy()), &mut diagnostics)) {
+            list_files.push((stem.to_string_lossy().into_owned(), contents));
+        }
+    }
+
+    let triggers = read(&data_dir()?.join(TRIGGERS_FILE), &format!("data/{}", TRIGGERS_FILE), &mut diagnostics);
+    let commands_text = read(&data_dir()?.join(COMMANDS_TEXT_FILE), &format!("data/{}", COMMANDS_TEXT_FILE), &mut diagnostics);
+    let commands = read(&config_dir()?.join(CONFIG_COMMANDS), &format!("config/{}", CONFIG_COMMANDS), &mut diagnostics);
+    let channels = read(&config_dir()?.join(CONFIG_CHANNELS), &format!("config/{}", CONFIG_CHANNELS), &mut diagnostics);
+
+    diagnostics.extend(check_contents(&list_files, triggers.as_deref(), commands_text.as_deref(), commands.as_deref(), channels.as_deref()));
+    Ok(diagnostics)
+}
+
+// the same, from file contents, lists given as (name, contents) like ListsMaps::parse takes them.
+// None for a file that couldn't be read, which has already been reported
+pub fn check_contents(list_files: &[(String, String)], triggers: Option<&str>, commands_text: Option<&str>, commands: Option<&str>, channels: Option<&str>) -> Vec<Diagnostic> {
+    let mut list_files: Vec<&(String, String)> = list_files.iter().collect();
+    list_files.sort();
+
+    let lists: HashMap<String, Vec<(usize, String)>> = list_files.iter()
+        .map(|(name, contents)| {
+            // without their weights, the way the bot keeps them
+            (name.clone(), list_entries(contents).map(|(line, entry)| (line, String::from(split_weight(entry).1))).collect())
+        })
+        .collect();
+
+    let mut checker = Checker {
+        lists: &lists,
+        diagnostics: Vec::new(),
+    };
+
+    for name in REQUIRED_LISTS {
+        if !lists.contains_key(name) {
+            checker.report(Severity::Error, &format!("data/{}.list", name), 0, String::from("missing required list"));
+        }
+    }
+    for (name, contents) in &list_files {
+        checker.check_options(&format!("data/{}.list", name), contents);
+        checker.check_weights(&format!("data/{}.list", name), contents);
+        checker.check_list(name, &format!("data/{}.list", name), &lists[name]);
+    }
+
+    let maps = [
+        (triggers, format!("data/{}", TRIGGERS_FILE), true),
+        (commands_text, format!("data/{}", COMMANDS_TEXT_FILE), false),
+        (commands, format!("config/{}", CONFIG_COMMANDS), false),
+    ];
+    for (contents, name, normalize_keys) in &maps {
+        if let Some(contents) = contents {
+            checker.check_map(name, contents, *normalize_keys);
+        }
+    }
+
+    if let Some(contents) = channels {
+        if list_lines(contents).next().is_none() {
+            checker.report(Severity::Warning, &format!("config/{}", CONFIG_CHANNELS), 0, String::from("no channels to join"));
+        }
+    }
+
+    checker.diagnostics
+}
+
+// warnings are things to look at, errors mean the bot won't do what the files say
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lists(extra: &[(&str, &str)]) -> Vec<(String, String)> {
+        [("passive_advice", "advice\n"), ("questions", "question?\n")].iter()
+            .chain(extra)
+            .map(|(name, contents)| (String::from(*name), String::from(*contents)))
+            .collect()
+    }
+
+    // the diagnostics for a triggers.map, as "file:line: severity: message"
+    fn check_triggers(extra: &[(&str, &str)], triggers: &str) -> Vec<String> {
+        check_contents(&lists(extra), Some(triggers), Some(""), Some(""), Some("chan\n")).iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn clean_files() {
+        let diagnostics = check_contents(&lists(&[("foods", "pizza\n")]), Some("hi=hello {user}\n[foods=yum {foods}\n"), Some(""), Some(""), Some("chan\n"));
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert!(!has_errors(&diagnostics));
+    }
+
+    #[test]
+    fn unknown_list() {
+        assert_eq!(check_triggers(&[], "[nope=hi\nhi=[nope\n"), [
+            "data/triggers.map:1: error: unknown list 'nope'",
+            "data/triggers.map:2: error: unknown list 'nope'",
+        ]);
+    }
+
+    #[test]
+    fn unknown_placeholder() {
+        assert_eq!(check_triggers(&[], "hi=hello {nope} {user}\n"), ["data/triggers.map:1: error: unknown placeholder '{nope}'"]);
+        // a binding is only known after it
+        assert_eq!(check_triggers(&[("foods", "pizza\n")], "hi={x} {foods:x} {x}\n"), ["data/triggers.map:1: error: unknown placeholder '{x}'"]);
+    }
+
+    #[test]
+    fn empty_list() {
+        assert_eq!(check_triggers(&[("foods", "-just a comment\n")], ""), ["data/foods.list: error: list is empty"]);
+    }
+
+    #[test]
+    fn duplicate_key() {
+        // compared the way they're stored, after normalizing and expanding lists
+        assert_eq!(check_triggers(&[("greetings", "Hello\n")], "hello=hi\n[greetings=hey\n"), [
+            "data/triggers.map:2: warning: duplicate key 'hello', first defined on line 1",
+        ]);
+    }
+
+    #[test]
+    fn near_too_short_for_the_words() {
+        // negative and repeated words don't need room
+        assert_eq!(check_triggers(&[], "a b c @near:2=hi\nd e -f @near:2=hi\ng g h @near:2=hi\nrust/go fast @near:2=hi\n"), [
+            "data/triggers.map:1: warning: key 'a b c' has 3 words, they never fit within @near:2 and it will never match",
+            "data/triggers.map:4: warning: key 'rust/go fast' has 3 words, they never fit within @near:2 and it will never match",
+        ]);
+    }
+
+    #[test]
+    fn capture_groups() {
+        assert_eq!(check_triggers(&[], "/i like (\\w+)/=me too, {1}\n/i like \\w+/={1}\nhi={1}\n"), [
+            "data/triggers.map:2: error: capture placeholder '{1}' but the regex only has 0 groups",
+            "data/triggers.map:3: error: capture placeholder '{1}' without a regex key",
+        ]);
+    }
+
+    #[test]
+    fn missing_files_are_only_reported_once() {
+        let diagnostics = check_contents(&lists(&[]), None, None, None, None);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        let diagnostics = check_contents(&[], Some(""), Some(""), Some(""), Some(""));
+        assert!(has_errors(&diagnostics));
+        assert_eq!(diagnostics.len(), 3, "{:?}", diagnostics);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::backend::{ ChatBackend, ChatMessage };
+use crate::bot::{ send_passive_advice, send_passive_question };
+use crate::data::{ parse_percent, regex_key, Entry, ListsMaps, MapValue };
+use crate::env::Env;
+use crate::state::{ MinMax, Mood, State, BACKOFF_ADVICE_INTERVAL, PASSIVE_ADVICE_INTERVAL };
+use crate::template::{ make_response_message, subst_context, subst_global };
+use crate::tokenize::normalize_key;
+use crate::triggers::handle_triggers;
+use itertools::Itertools;
+use std::borrow::{ Borrow, Cow };
+use std::collections::HashSet;
+use std::error::Error;
+use std::time::Duration;
+
+pub const TRIGGER_MESSAGES: bool = true;
+pub const COMMAND_MESSAGES: bool = true;
+
+pub async fn parse_command(state: &mut State, lm: &mut ListsMaps, env: &mut Env, backend: &mut impl ChatBackend, msg: &ChatMessage) -> Result<(), Box<dyn Error>> {
+    let channel = &msg.channel[..];
+    if COMMAND_MESSAGES {
+        let cstate = if let Some( cstate ) = state.channels.get_mut(&State::chash(channel)) {
+            cstate
+        } else {
+            println!("parse_command: failed to find channel {}", channel );
+            return Err("parse command failed to find channel".into());
+        }; 
+
+        let mut was_command = false;
+        if let Some(Entry { value: MapValue::Value(command_text), options }) = lm.command_text.get(msg.text.as_str()) {
+            println!("got command {}", command_text);
+            if cstate.roll_chance(env, &msg.text, options.chance) {
+                let result = subst_context(cstate, &msg.user, &msg.text, Cow::Borrowed(&**command_text));
+                cstate.force_send_message(env, backend, &result).await;
+            } else {
+                println!("command {} didn't roll its chance", msg.text);
+            }
+            was_command = true;
+        }
+
+        let mut commands = msg.text.split_whitespace();
+
+        if let Some(Entry { value: MapValue::Value(command), .. }) = lm.commands.get(commands.next().unwrap_or("")) {
+            match &**command {
+                "COMMANDS" => {
+                    let keys: HashSet<&str> = lm.command_text.keys().chain( lm.commands.keys() ).map(|k| k.borrow()).collect();
+                    let msg: String = keys.iter().sorted().join(", ");
+                    cstate.force_send_message(env, backend, &msg).await; 
+                    return Ok(());
+                }
+                "CONFIG" => {
+                    // not lowercased, a regex's \W and \w are different things
+                    let mut iter = msg.text.split_whitespace();
+                    iter.next(); // ignore the command, which would be the substituted "CONFIG"
+                    
+                    match iter.next().map(str::to_lowercase).as_deref() {
+                        Some("cd") => {
+                            let error_msg = "invalid command, expected format \"cd <min> <max>\" where <min> and <max> are integer numbers";
+                            if let (Some(min_str), Some(max_str)) = (iter.next(), iter.next()) {
+                                if let (Ok(a), Ok(b)) = (min_str.parse::<u64>(), max_str.parse::<u64>()) {
+                                    let min = if a > b { b } else { a };
+                                    let max = if a > b { a } else { b }; 
+                                    cstate.next_message = MinMax::new(Duration::from_secs(min), Duration::from_secs(max));
+                                    cstate.force_send_message(env, backend, &format!("successfully changed message cooldown to {}s-{}s", min, max)).await;
+                                } else {
+                                    cstate.force_send_message(env, backend, error_msg).await;
+                                }
+                            } else {
+                                cstate.force_send_message(env, backend, error_msg).await;
+                            }
+                        }
+                        Some("chance") => {
+                            if !msg.is_privileged() {
+                                println!("ignoring CONFIG chance from {}, who isn't a mod", msg.user);
+                                return Ok(());
+                            }
+                            // "chance <percent>" for every trigger, "chance <trigger> <percent>" for one
+                            let error_msg = "invalid command, expected format \"chance [<trigger>] <percent>\" where <percent> is 0 to 100";
+                            let args: Vec<&str> = iter.collect();
+                            let response = match args.split_last().map(|(percent, trigger)| (parse_percent(percent), trigger.join(" "))) {
+                                Some((Ok(percent), trigger)) if trigger.is_empty() => {
+                                    cstate.chance = Some(percent);
+                                    format!("successfully changed the chance of answering triggers to {}%", percent)
+                                }
+                                Some((Ok(percent), trigger)) => {
+                                    // the trigger as it's written in triggers.map, turned into the key it's stored under
+                                    let trigger = match regex_key(&trigger) {
+                                        Some(pattern) => String::from(pattern),
+                                        None if trigger.contains(' ') => subst_global(Cow::Borrowed(&trigger)).to_lowercase(),
+                                        None => normalize_key(&subst_global(Cow::Borrowed(&trigger))),
+                                    };
+                                    let response = format!("successfully changed the chance of answering '{}' to {}%", trigger, percent);
+                                    cstate.trigger_chances.insert(trigger, percent);
+                                    response
+                                }
+                                _ => String::from(error_msg),
+                            };
+                            cstate.force_send_message(env, backend, &response).await;
+                        }
+                        _ => {
+                            println!("detected unknown CONFIG subcommand in '{}'", &msg.text);
+                        } 
+                    } 
+                    
+                    return Ok(());
+                }
+                "RELOAD" => {
+                    if !msg.is_privileged() {
+                        println!("ignoring RELOAD from {}, who isn't a mod", msg.user);
+                        return Ok(());
+                    }
+
+                    let response = match ListsMaps::load() {
+                        Ok(new_lm) => {
+                            let response = format!("reloaded {}", new_lm.summary());
+                            *lm = new_lm;
+                            response
+                        }
+                        Err(e) => format!("reload failed, keeping the old data: {:#}", e),
+                    };
+                    cstate.force_send_message(env, backend, &response).await;
+                    return Ok(());
+                }
+                "LEAVE" => {
+                    cstate.next_advice = BACKOFF_ADVICE_INTERVAL;
+                    state.set_mood(channel, Mood::Backoff);
+                    return Ok(());
+                }
+                "JOIN" => {
+                    cstate.next_advice = PASSIVE_ADVICE_INTERVAL;
+                    state.set_mood(channel, Mood::Normal);
+                    return Ok(());
+                }
+                "RANDOM_STATEMENT" => { 
+                    send_passive_advice(cstate, lm, env, backend, true).await;
+                    return Ok(());
+                }
+                "RANDOM_QUESTION" => { 
+                    send_passive_question(cstate, lm, env, backend, true).await;
+                    return Ok(());
+                }
+                "IGNORE_ME" => { 
+                    state.ignores.insert(msg.user.clone());
+                    return Ok(());
+                }
+                "NOTICE_ME" => { 
+                    state.ignores.remove(&msg.user);
+                    return Ok(());
+                }
+                "OFF_TOPIC" => { 
+                    let response = match cstate.start_off_topic(env) {
+                        Some(duration) => {
+                            format!("{channel} has already been off topic for {}h {}m {}s",
+                                    duration.as_secs() / 60 / 60,
+                                    duration.as_secs() / 60 % 60,
+                                    duration.as_secs() % 60 )
+                        }
+                        None => {
+                            "starting off topic timer".to_string()
+                        }
+                    };
+
+                    let response = make_response_message(cstate, lm, env, &msg.user, "OFF_TOPIC", &response);
+                    cstate.force_send_message(env, backend, &response).await;
+                    return Ok(());
+                }
+                "ON_TOPIC" => { 
+                    if let Some(duration) = cstate.end_off_topic(env) {
+                        let response = format!("{channel} is finally on topic, it took them {}h {}m {}s", duration.as_secs() / 60 / 60, duration.as_secs() / 60 % 60, duration.as_secs() % 60 );
+                        let response = make_response_message(cstate, lm, env, &msg.user, "ON_TOPIC", &response);
+                        cstate.force_send_message(env, backend, &response).await;
+                    }
+                    return Ok(());
+                }
+                "TOTAL_OFF_TOPIC" => { 
+                    let response = format!("The streamer has been off topic a total of {}h {}m {}s", cstate.total_off_topic.as_secs() / 60 / 60, cstate.total_off_topic.as_secs() / 60 % 60, cstate.total_off_topic.as_secs() % 60 );
+                    let response = make_response_message(cstate, lm, env, &msg.user, "TOTAL_OFF_TOPIC", &response);
+                    cstate.force_send_message(env, backend, &response).await;
+                    return Ok(());
+                }
+                "SET_TOPIC" => { 
+                    let topic = commands.next().unwrap_or("");
+                    let response = format!("current topic is now {}", topic);
+                    let response = make_response_message(cstate, lm, env, &msg.user, "SET_TOPIC", &response);
+                    cstate.force_send_message(env, backend, &response).await;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+
+        if was_command {
+            return Ok(());
+        }
+    }
+
+
+    if TRIGGER_MESSAGES {
+        handle_triggers( state, lm, env, backend, msg ).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,643 @@
+use crate::matcher::TriggerMatcher;
+use crate::template::subst_global;
+use crate::tokenize::{ normalize, normalize_key };
+use anyhow::Context as _;
+use rand::Rng;
+use regex::{ Regex, RegexBuilder };
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::{ self, File };
+use std::io::prelude::*;
+use std::path::{ Path, PathBuf };
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const TRIGGERS_FILE: &str = "triggers.map";
+pub const CONFIG_CHANNELS: &str = "channels.list";
+pub const CONFIG_COMMANDS: &str = "commands.map";
+pub const COMMANDS_TEXT_FILE: &str = "commands_text.map";
+
+// lists the bot picks from on its own, it can't run without them
+pub const REQUIRED_LISTS: [&str; 2] = ["passive_advice", "questions"];
+
+pub fn parse_list(contents: &str) -> Vec<&str> {
+    let mut data = Vec::new();
+    for line in contents.lines() {
+        if line.is_empty() { continue; }
+        if let Some('-') = line.chars().next() {
+            continue;
+        }
+        data.push(line); 
+    } 
+    data
+}
+
+// the "5" and "text" of "5|text", when everything before the | is digits
+pub fn weight_prefix(line: &str) -> Option<(&str, &str)> {
+    line.split_once('|').filter(|(weight, _)| !weight.is_empty() && weight.chars().all(|c| c.is_ascii_digit()))
+}
+
+// a line of a .list file can start with a weight, "5|text" comes up five times as often as a line
+// without one, and "0|text" never does. anything else before a | is part of the text. a weight too
+// big for a u32 is the biggest there is, check reports those
+pub fn split_weight(line: &str) -> (u32, &str) {
+    match weight_prefix(line) {
+        Some((weight, text)) => (weight.parse().unwrap_or(u32::MAX), text),
+        None => (1, line),
+    }
+}
+
+// how a list keeps from repeating itself in a channel, set with a line of its own in the file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Repeat {
+    #[default]
+    Random,
+    // @shuffle, every entry comes up once before any of them come up again, so weights only change
+    // the order they come up in
+    Shuffle,
+    // @avoid:N, none of the last N picks
+    Avoid(usize),
+}
+
+// a line like "@shuffle" or "@avoid:5". lines starting with @ are entries otherwise, "@{user} hi"
+pub fn list_option(line: &str) -> Option<Repeat> {
+    match line.strip_prefix('@')?.split_once(':') {
+        None if line == "@shuffle" => Some(Repeat::Shuffle),
+        Some(("avoid", count)) => count.parse().ok().map(Repeat::Avoid),
+        _ => None,
+    }
+}
+
+pub struct List {
+    pub entries: Vec<Arc<str>>,
+    weights: Vec<u32>,
+    total: usize,
+    pub repeat: Repeat,
+}
+
+impl List {
+    pub fn parse(contents: &str) -> Self {
+        let mut repeat = Repeat::Random;
+        let (weights, entries): (Vec<u32>, Vec<Arc<str>>) = parse_list(contents).into_iter()
+            .filter(|line| match list_option(line) {
+                Some(option) => {
+                    repeat = option;
+                    false
+                }
+                None => true,
+            })
+            .map(|line| {
+                let (weight, text) = split_weight(line);
+                (weight, Arc::from(text))
+            })
+            .unzip();
+        List {
+            total: weights.iter().map(|weight| *weight as usize).sum(),
+            entries,
+            weights,
+            repeat,
+        }
+    }
+
+    fn pick_from(&self, rng: &mut impl Rng, total: usize, allowed: impl Fn(usize) -> bool) -> Option<usize> {
+        if total == 0 {
+            return None;
+        }
+        let mut n = rng.gen::<usize>() % total;
+        for (index, weight) in self.weights.iter().enumerate() {
+            if !allowed(index) { continue; }
+            let weight = *weight as usize;
+            if n < weight {
+                return Some(index);
+            }
+            n -= weight;
+        }
+        None
+    }
+
+    // every random pick from a list goes through here. recent is what this channel picked from the
+    // list lately, for lists that avoid repeats. None when nothing can be picked
+    pub fn pick(&self, rng: &mut impl Rng, recent: Option<&mut Vec<String>>) -> Option<&Arc<str>> {
+        let recent = match (self.repeat, recent) {
+            (Repeat::Random, _) | (_, None) => return self.pick_from(rng, self.total, |_| true).map(|index| &self.entries[index]),
+            (_, Some(recent)) => recent,
+        };
+
+        // entries are remembered by their text, so an edit to the file doesn't mix them up
+        let fresh = |recent: &[String], index: usize| !recent.iter().any(|entry| **entry == *self.entries[index]);
+        let total = loop {
+            let total: usize = (0..self.entries.len())
+                .filter(|index| fresh(recent, *index))
+                .map(|index| self.weights[index] as usize)
+                .sum();
+            if total > 0 || recent.is_empty() { break total; }
+            match self.repeat {
+                // everything has come up, start over
+                Repeat::Shuffle => recent.clear(),
+                // fewer entries than N, the oldest can come up again
+                _ => { recent.remove(0); }
+            }
+        };
+
+        let index = self.pick_from(rng, total, |index| fresh(recent, index))?;
+        recent.push(String::from(&*self.entries[index]));
+        if let Repeat::Avoid(count) = self.repeat {
+            let excess = recent.len().saturating_sub(count);
+            recent.drain(..excess);
+        }
+        Some(&self.entries[index])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MapValue {
+    FileName(Arc<str>),
+    Value(Arc<str>),
+}
+
+// how the words of a multi word trigger have to show up in a message. words are always compared
+// whole, "mom" doesn't match "moment"
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchMode {
+    // every word, anywhere, in any order
+    #[default]
+    AnyOrder,
+    // the words next to each other, in the order they're written. set with @ordered
+    Ordered,
+    // every word, in any order, within a stretch of this many words. set with @near:N
+    Near(usize),
+}
+
+// settings given as @name:value words at the end of a key, like "mom @cooldown:5m=[mom_jokes"
+#[derive(Debug, Clone, Default)]
+pub struct TriggerOptions {
+    // how long before the same trigger can fire again in a channel
+    pub cooldown: Option<Duration>,
+    pub match_mode: MatchMode,
+    // when a message sets off more than one trigger, the highest priority answers
+    pub priority: i32,
+    // percent of the time the trigger answers, None is always
+    pub chance: Option<u32>,
+    // how many typos a word can have and still match, None is exact
+    pub fuzzy: Option<usize>,
+    // what the cooldown is kept under, when it isn't the key. every key from a [list line shares
+    // the list's, so "[moms @cooldown:5m" is one cooldown and not one per spelling
+    pub cooldown_key: Option<Arc<str>>,
+}
+
+impl TriggerOptions {
+    fn set(&mut self, option: &str) -> anyhow::Result<()> {
+        match option.split_once(':').unwrap_or((option, "")) {
+            ("@cooldown", value) => self.cooldown = Some(parse_duration(value)?),
+            ("@priority", value) => {
+                self.priority = value.parse().with_context(|| format!("invalid priority '{}'", value))?;
+            }
+            ("@fuzzy", "") => self.fuzzy = Some(1),
+            ("@fuzzy", value) => {
+                self.fuzzy = Some(value.parse().with_context(|| format!("invalid typo count '{}'", value))?);
+            }
+            ("@chance", value) => self.chance = Some(parse_percent(value)?),
+            ("@ordered", "") => self.match_mode = MatchMode::Ordered,
+            ("@near", value) => {
+                let words = value.parse().with_context(|| format!("invalid word count '{}'", value))?;
+                if words == 0 {
+                    anyhow::bail!("@near needs at least 1 word");
+                }
+                self.match_mode = MatchMode::Near(words);
+            }
+            _ => anyhow::bail!("unknown option '{}'", option),
+        }
+        Ok(())
+    }
+}
+
+// 0 to 100, the % is optional
+pub fn parse_percent(value: &str) -> anyhow::Result<u32> {
+    let percent = value.strip_suffix('%').unwrap_or(value);
+    match percent.parse() {
+        Ok(percent) if percent <= 100 => Ok(percent),
+        _ => anyhow::bail!("invalid percent '{}', expected 0 to 100", value),
+    }
+}
+
+// seconds, or a number followed by s, m or h
+pub fn parse_duration(value: &str) -> anyhow::Result<Duration> {
+    let (number, unit) = match value.char_indices().last() {
+        Some((i, 's')) => (&value[..i], 1),
+        Some((i, 'm')) => (&value[..i], 60),
+        Some((i, 'h')) => (&value[..i], 60 * 60),
+        _ => (value, 1),
+    };
+    let number: u64 = number.parse().with_context(|| format!("invalid duration '{}'", value))?;
+    match number.checked_mul(unit) {
+        Some(seconds) => Ok(Duration::from_secs(seconds)),
+        None => anyhow::bail!("duration '{}' is too long", value),
+    }
+}
+
+// what can go at the end of a key, anything else starting with @ is part of the key, hey @{me}
+const OPTIONS: [&str; 6] = ["@cooldown", "@priority", "@chance", "@fuzzy", "@ordered", "@near"];
+
+fn is_option(word: &str) -> bool {
+    OPTIONS.contains(&word.split_once(':').map_or(word, |(name, _)| name))
+}
+
+// strips the @options off the end of a key
+pub fn parse_key(meta_key: &str) -> anyhow::Result<(&str, TriggerOptions)> {
+    let mut key = meta_key.trim_end();
+    let mut options = TriggerOptions::default();
+    while let Some((rest, option)) = key.rsplit_once(' ') {
+        if !is_option(option) { break; }
+        options.set(option)?;
+        key = rest.trim_end();
+    }
+    Ok((key, options))
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub value: MapValue,
+    pub options: TriggerOptions,
+}
+
+pub type Map = HashMap<Arc<str>, Entry>;
+
+#[derive(Debug)]
+pub struct MultiTrigger {
+    // the key as written, without options
+    pub key: Arc<str>,
+    pub triggers: Vec<Arc<str>>,
+    // words written with a leading -, the trigger doesn't match if any of them are in the message
+    pub negative: Vec<Arc<str>>,
+    pub value: MapValue, 
+    pub options: TriggerOptions,
+}
+
+// a /pattern/ key, matched against the message ignoring case. the value can use the capture groups
+// as {1}, {2}..
+#[derive(Debug)]
+pub struct RegexTrigger {
+    pub regex: Regex,
+    pub value: MapValue,
+    pub options: TriggerOptions,
+}
+
+#[derive(Debug, Default)]
+pub struct LoadedMap {
+    pub map: Map,
+    pub multi_triggers: Vec<MultiTrigger>,
+    pub regex_triggers: Vec<RegexTrigger>,
+}
+
+// the pattern of a /pattern/ key
+pub fn regex_key(key: &str) -> Option<&str> {
+    key.strip_prefix('/')?.strip_suffix('/')
+}
+
+// regex keys ignore case like every other key, /Java/ matches "java" too
+pub fn compile_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+// splits a map line into key and value. a regex can contain = itself, so its key runs up to the
+// first / followed by = or options instead. without one it's a plain key that starts with /, "/shrug"
+pub fn split_line(line: &str) -> Option<(&str, &str)> {
+    if let Some(rest) = line.strip_prefix('/') {
+        let split = rest.match_indices('/')
+            .map(|(i, _)| i + 2)
+            .find(|&end| line[end..].starts_with('=') || line[end..].starts_with(" @"))
+            .and_then(|end| Some(end + line[end..].find('=')?));
+        if let Some(split) = split {
+            return Some((&line[..split], &line[split + 1..]));
+        }
+    }
+    let mut split = line.split('='); 
+    match (split.next(), split.next()) {
+        (Some(key), Some(value)) => Some((key, value)),
+        _ => None,
+    }
+}
+
+// normalize_keys puts keys through the same normalizing as messages, for maps that are matched
+// against chat. literal keys and keys from lists are treated the same
+pub fn load_map(contents: &str, lists: &HashMap<Arc<str>, List>, normalize_keys: bool) -> anyhow::Result<LoadedMap> {
+    let mut map = HashMap::new();
+    let mut multi_triggers = Vec::new();
+    let mut regex_triggers = Vec::new();
+    for (line_number, line) in contents.lines().enumerate() {
+        if let Some((meta_key, value)) = split_line(line) {
+            if meta_key.is_empty() { continue; }
+            if value.is_empty() { continue; }
+            if let Some('-') = meta_key.chars().next() {
+                continue;
+            }
+            let (meta_key, options) = parse_key(meta_key)
+                .with_context(|| format!("line {}", line_number + 1))?;
+
+            let map_value = if let Some('[') = value.chars().next() {
+                MapValue::FileName(Arc::from(&value[1..])) 
+            } else {
+                MapValue::Value(Arc::from(value)) 
+            };
+
+            if let Some(pattern) = regex_key(meta_key) {
+                let regex = compile_regex(pattern)
+                    .with_context(|| format!("line {}: invalid regex '{}'", line_number + 1, pattern))?;
+                regex_triggers.push(RegexTrigger { regex, value: map_value, options });
+                continue;
+            }
+
+            // the starting character can be a meta key, if the meta_key is a forward square
+            // bracket, then the key is pointing to a list file. treat each entry as a key
+            let mut options = options;
+            if meta_key.starts_with('[') {
+                options.cooldown_key = Some(Arc::from(meta_key));
+            }
+            let single = vec![Arc::from(meta_key)];
+            let keys = if let Some('[') = meta_key.chars().next() {
+                match lists.get(&meta_key[1..]) {
+                    Some(list) => &list.entries,
+                    None => anyhow::bail!("line {}: unknown list '{}'", line_number + 1, &meta_key[1..]),
+                }
+            } else {
+                &single
+            };
+
+            // words with placeholders left in them are normalized after they're filled in
+            let normalize_words = |words: Vec<&str>| -> Vec<Arc<str>> {
+                words.into_iter()
+                    .flat_map(|word| if word.contains('{') || !normalize_keys {
+                        vec![String::from(word)]
+                    } else {
+                        normalize(word)
+                    })
+                    .map(Arc::from)
+                    .collect()
+            };
+
+            'key_loop: for key in keys { 
+                let key = subst_global(Cow::Borrowed(key));
+                if key.contains(' ') {
+                    let (negative, triggers): (Vec<&str>, Vec<&str>) = key.split_whitespace()
+                        .partition(|word| word.len() > 1 && word.starts_with('-'));
+                    let negative = negative.into_iter().map(|word| &word[1..]).collect();
+                    let triggers = normalize_words(triggers);
+                    if triggers.is_empty() { continue 'key_loop; }
+
+                    multi_triggers.push(MultiTrigger { 
+                        key: Arc::from(if normalize_keys { key.to_lowercase() } else { key.to_string() }),
+                        triggers,
+                        negative: normalize_words(negative),
+                        value: map_value.clone(),
+                        options: options.clone(),
+                    }); 
+                } else {
+                    if key.contains('{') { continue 'key_loop; }
+                    let key = if normalize_keys { normalize_key(&key) } else { key.into_owned() };
+                    map.insert(Arc::from(key), Entry {
+                        value: map_value.clone(),
+                        options: options.clone(),
+                    });
+                }
+            }
+        }
+    } 
+    Ok(LoadedMap {
+        map,
+        multi_triggers,
+        regex_triggers,
+    })
+}
+
+pub fn data_dir() -> anyhow::Result<PathBuf> {
+    Ok(std::env::current_dir()?.join("data"))
+}
+
+pub fn load_file_rel(name: &str) -> anyhow::Result<String> { 
+    let full_path = data_dir()?.join(name);
+    load_file(&full_path)
+}
+
+pub fn config_dir() -> anyhow::Result<PathBuf> {
+    Ok(std::env::current_dir()?.join("config"))
+}
+
+pub fn load_config_file(name: &str) -> anyhow::Result<String> { 
+    let full_path = config_dir()?.join(name);
+    load_file(&full_path)
+}
+
+pub fn load_file(full_path: &Path) -> anyhow::Result<String> {
+    println!("path {:?}", full_path);
+    let mut file = File::open(full_path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+// every .list file in the data dir, as (name, contents)
+pub fn load_list_files() -> anyhow::Result<Vec<(String, String)>> {
+    let dir = fs::read_dir(data_dir()?)?;
+    let mut contents = Vec::new();
+    for file in dir.filter_map(|file| file.ok()) {
+        let path = file.path();
+        if let Some(ext) = path.extension() {
+            if ext != "list" { continue; }
+            let content = load_file(&path)?;
+            contents.push((String::from(path.file_stem().unwrap().to_str().unwrap()), content));
+        }
+    } 
+    Ok(contents)
+}
+
+pub fn user_dir() -> anyhow::Result<PathBuf> {
+    let mut path = dirs::home_dir().unwrap();
+    path.push("cynobot");
+    Ok(path)
+}
+
+// everything the bot says and reacts to. it owns its data, so a fresh copy can be built and
+// swapped in while the bot is running
+pub struct ListsMaps {
+    pub commands: Map,
+    pub command_text: Map,
+    pub lists: HashMap<Arc<str>, List>,
+    pub multi_triggers: Vec<MultiTrigger>,
+    pub regex_triggers: Vec<RegexTrigger>,
+    pub triggers: Map,
+    // built from the triggers above, for matching messages quickly
+    pub matcher: TriggerMatcher,
+}
+
+impl ListsMaps {
+    pub fn new(
+        commands: Map,
+        command_text: Map,
+        lists: HashMap<Arc<str>, List>,
+        multi_triggers: Vec<MultiTrigger>, 
+        regex_triggers: Vec<RegexTrigger>, 
+        triggers: Map, 
+    ) -> anyhow::Result<Self> {
+        let matcher = TriggerMatcher::new(&triggers, &multi_triggers, &regex_triggers)?;
+        Ok(ListsMaps {
+            commands,
+            command_text,
+            lists,
+            multi_triggers,
+            regex_triggers,
+            triggers,
+            matcher,
+        })
+    }
+
+    // read everything in the data and config dirs
+    pub fn load() -> anyhow::Result<Self> {
+        let list_files = load_list_files()?;
+        let triggers = load_file_rel(TRIGGERS_FILE)?;
+        let commands_text = load_file_rel(COMMANDS_TEXT_FILE)?;
+        let commands = load_config_file(CONFIG_COMMANDS)?;
+        ListsMaps::parse(&list_files, &triggers, &commands_text, &commands)
+    }
+
+    // build from file contents, lists are given as (name, contents)
+    pub fn parse(list_files: &[(String, String)], triggers: &str, commands_text: &str, commands: &str) -> anyhow::Result<Self> {
+        let mut lists = HashMap::new();
+        for (name, content) in list_files {
+            lists.insert(Arc::from(&name[..]), List::parse(content));
+        }
+        for name in REQUIRED_LISTS {
+            if !lists.contains_key(name) {
+                anyhow::bail!("missing required list '{}.list'", name);
+            }
+        }
+
+        let triggers = load_map(triggers, &lists, true)
+            .with_context(|| format!("failed to load {}", TRIGGERS_FILE))?; 
+
+        // map a command to some text the user sees
+        let commands_text = load_map(commands_text, &lists, false)
+            .with_context(|| format!("failed to load {}", COMMANDS_TEXT_FILE))?; 
+
+        // map a command to a code operation
+        let commands = load_map(commands, &lists, false)
+            .with_context(|| format!("failed to load {}", CONFIG_COMMANDS))?; 
+
+        ListsMaps::new(commands.map, commands_text.map, lists, triggers.multi_triggers, triggers.regex_triggers, triggers.map)
+    }
+
+    pub fn summary(&self) -> String {
+        format!("{} lists, {} triggers, {} multi triggers, {} regex triggers, {} commands",
+                self.lists.len(),
+                self.triggers.len(),
+                self.multi_triggers.len(),
+                self.regex_triggers.len(),
+                self.commands.len() + self.command_text.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Env;
+    use crate::state::ChannelState;
+    use std::collections::HashSet;
+
+    fn picks(list: &List, env: &mut Env, recent: &mut Vec<String>, count: usize) -> Vec<String> {
+        (0..count).map(|_| String::from(&**list.pick(&mut env.rng, Some(recent)).unwrap())).collect()
+    }
+
+    fn distinct(picks: &[String]) -> usize {
+        picks.iter().collect::<HashSet<_>>().len()
+    }
+
+    #[test]
+    fn shuffle_goes_through_the_whole_list_first() {
+        let list = List::parse("@shuffle\na\nb\nc\nd\ne\n");
+        let (mut env, _clock) = Env::manual(5);
+        let mut recent = Vec::new();
+        for _ in 0..10 {
+            assert_eq!(distinct(&picks(&list, &mut env, &mut recent, 5)), 5);
+        }
+    }
+
+    #[test]
+    fn shuffle_carries_on_after_a_restart() {
+        let list = List::parse("@shuffle\na\nb\nc\nd\ne\n");
+        let (mut env, _clock) = Env::manual(5);
+        let mut cstate = ChannelState::new("chan", &env);
+        let recent = cstate.list_history.entry(String::from("letters")).or_default();
+        let mut round = picks(&list, &mut env, recent, 2);
+
+        // the history is saved with the rest of the channel's state
+        let saved = serde_json::to_string(&cstate).unwrap();
+        let mut cstate: ChannelState = serde_json::from_str(&saved).unwrap();
+        let recent = cstate.list_history.get_mut("letters").unwrap();
+        round.extend(picks(&list, &mut env, recent, 3));
+        assert_eq!(distinct(&round), 5, "{:?}", round);
+    }
+
+    #[test]
+    fn avoid_more_than_the_list_has() {
+        let list = List::parse("@avoid:10\na\nb\nc\n");
+        assert_eq!(list.repeat, Repeat::Avoid(10));
+        let (mut env, _clock) = Env::manual(5);
+        let mut recent = Vec::new();
+        // it can't avoid 10, but it still picks the one that came up longest ago
+        let picked = picks(&list, &mut env, &mut recent, 30);
+        for window in picked.windows(3) {
+            assert_eq!(distinct(window), 3, "{:?}", picked);
+        }
+        assert!(recent.len() <= 3);
+    }
+
+    #[test]
+    fn weights_set_how_often_entries_come_up() {
+        let list = List::parse("8|often\nsometimes\n1|also sometimes\n0|never\n99999999999|also never\n");
+        assert_eq!(list.weights[4], u32::MAX);
+        let list = List::parse("8|often\nsometimes\n1|also sometimes\n0|never\n");
+        let (mut env, _clock) = Env::manual(5);
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for _ in 0..10000 {
+            *counts.entry(String::from(&**list.pick(&mut env.rng, None).unwrap())).or_default() += 1;
+        }
+        // out of 10 shares
+        assert!((7500..8500).contains(&counts["often"]), "{:?}", counts);
+        for entry in ["sometimes", "also sometimes"] {
+            assert!((750..1250).contains(&counts[entry]), "{:?}", counts);
+        }
+        assert!(!counts.contains_key("never"));
+
+        // nothing to pick from
+        let list = List::parse("0|off\n0|also off\n");
+        assert_eq!(list.pick(&mut env.rng, None), None);
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("9999999999999999h").is_err());
+    }
+
+    #[test]
+    fn an_overflowing_cooldown_is_a_load_error() {
+        let lists = vec![
+            (String::from("passive_advice"), String::from("advice\n")),
+            (String::from("questions"), String::from("question?\n")),
+        ];
+        let e = ListsMaps::parse(&lists, "hi=hello\nmom @cooldown:9999999999999999h=joke\n", "", "").err().unwrap();
+        assert!(format!("{:#}", e).contains("line 2: duration '9999999999999999h' is too long"), "{:#}", e);
+    }
+
+    #[test]
+    fn split_lines() {
+        assert_eq!(split_line("hi=hello"), Some(("hi", "hello")));
+        assert_eq!(split_line("/a=b/=c"), Some(("/a=b/", "c")));
+        assert_eq!(split_line("/a=b/ @chance:5=c"), Some(("/a=b/ @chance:5", "c")));
+        // no closing /, so not a regex
+        assert_eq!(split_line("/shrug=meh"), Some(("/shrug", "meh")));
+        assert_eq!(split_line("no value"), None);
+    }
+}
@@ -0,0 +1,73 @@
+use rand::rngs::StdRng;
+use rand::{ RngCore, SeedableRng };
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+// a clock that only moves when told to. clones share the same time
+#[derive(Clone)]
+pub struct ManualClock {
+    now: Rc<Cell<SystemTime>>,
+}
+
+impl ManualClock {
+    pub fn new(now: SystemTime) -> Self {
+        ManualClock {
+            now: Rc::new(Cell::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        self.now.set(now);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        self.now.get()
+    }
+}
+
+// where the bot gets the time and its randomness from. the bot itself should never call
+// SystemTime::now() or rand::thread_rng(), so runs can be made repeatable
+pub struct Env {
+    pub clock: Box<dyn Clock>,
+    pub rng: Box<dyn RngCore>,
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Env::new(SystemClock, StdRng::from_entropy())
+    }
+}
+
+impl Env {
+    pub fn new(clock: impl Clock + 'static, rng: impl RngCore + 'static) -> Self {
+        Env {
+            clock: Box::new(clock),
+            rng: Box::new(rng),
+        }
+    }
+
+    // a seeded rng and a clock stopped at the epoch. the returned clock handle moves it
+    pub fn manual(seed: u64) -> (Self, ManualClock) {
+        let clock = ManualClock::new(UNIX_EPOCH);
+        (Env::new(clock.clone(), StdRng::seed_from_u64(seed)), clock)
+    }
+
+    pub fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+}
@@ -0,0 +1,12 @@
+pub mod backend;
+pub mod bot;
+pub mod check;
+pub mod commands;
+pub mod data;
+pub mod env;
+pub mod matcher;
+pub mod reload;
+pub mod state;
+pub mod template;
+pub mod tokenize;
+pub mod triggers;
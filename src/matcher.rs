@@ -0,0 +1,501 @@
+use crate::data::{ Map, MatchMode, MultiTrigger, RegexTrigger, TriggerOptions };
+use regex::{ RegexSet, RegexSetBuilder };
+use std::collections::{ HashMap, HashSet };
+use std::sync::Arc;
+
+// a key gets one typo for every this many letters. short words are too easy to hit by accident,
+// "shift" is one typo away from "swift" and "data" from "dota", so they have to be exact
+pub const LETTERS_PER_TYPO: usize = 6;
+
+// the number of typos between a token and a key, if it's within the allowed amount. @fuzzy:N is
+// the most a key gets, however long it is. the length check first skips most keys without working
+// out the distance
+fn typos(token: &str, key: &str, allowed: usize) -> Option<usize> {
+    let (token_len, key_len) = (token.chars().count(), key.chars().count());
+    let allowed = allowed.min(key_len / LETTERS_PER_TYPO);
+    if allowed == 0 || token_len.abs_diff(key_len) > allowed {
+        return None;
+    }
+    // a longer word that starts with the key is a different word, "personal" isn't a typo of "persona"
+    if token_len > key_len && token.starts_with(key) {
+        return None;
+    }
+    if allowed == 1 {
+        return one_typo(token, key);
+    }
+    Some(strsim::osa_distance(token, key)).filter(|typos| *typos <= allowed)
+}
+
+// osa_distance when all that matters is whether it's 1 or less, which is almost every @fuzzy word,
+// without building its table. past the common start and end, what's left has to be one letter
+// added, dropped or changed, or two swapped
+fn one_typo(token: &str, key: &str) -> Option<usize> {
+    if token == key {
+        return Some(0);
+    }
+    let start = token.char_indices().zip(key.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(token.len().min(key.len()), |((i, _), _)| i);
+    let (token, key) = (&token[start..], &key[start..]);
+    let end = token.chars().rev().zip(key.chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+    let (token, key) = (&token[..token.len() - end], &key[..key.len() - end]);
+    let mut swapped = key.chars().rev();
+    match (token.chars().count(), key.chars().count()) {
+        (0, 1) | (1, 0) | (1, 1) => Some(1),
+        (2, 2) if token.chars().all(|c| swapped.next() == Some(c)) => Some(1),
+        _ => None,
+    }
+}
+
+fn same_word(token: &str, word: &str, fuzzy: Option<usize>) -> bool {
+    token == word || fuzzy.is_some_and(|allowed| typos(token, word, allowed).is_some())
+}
+
+fn contains_all<S: AsRef<str>>(tokens: &[&str], words: &[S], fuzzy: Option<usize>) -> bool {
+    words.iter().all(|word| tokens.iter().any(|token| same_word(token, word.as_ref(), fuzzy)))
+}
+
+fn words_match<S: AsRef<str>>(options: &TriggerOptions, tokens: &[&str], words: &[S]) -> bool {
+    if words.is_empty() {
+        return false;
+    }
+    let fuzzy = options.fuzzy;
+    match options.match_mode {
+        MatchMode::AnyOrder => contains_all(tokens, words, fuzzy),
+        MatchMode::Ordered => tokens.windows(words.len())
+            .any(|window| window.iter().zip(words).all(|(token, word)| same_word(token, word.as_ref(), fuzzy))),
+        MatchMode::Near(distance) => {
+            // a message shorter than the stretch is one window
+            let size = distance.min(tokens.len()).max(1);
+            tokens.windows(size).any(|window| contains_all(window, words, fuzzy))
+        }
+    }
+}
+
+// checks one multi word trigger against the message's tokens. fill turns a word with placeholders
+// into normalized words. returns how many words the trigger matched
+pub fn multi_trigger_matches(multi_trigger: &MultiTrigger, tokens: &[&str], fill: &impl Fn(&str) -> Vec<String>) -> Option<usize> {
+    let fill_all = |words: &[Arc<str>]| -> Vec<String> {
+        words.iter()
+            .flat_map(|word| if word.contains('{') { fill(word) } else { vec![String::from(&**word)] })
+            .collect()
+    };
+    let trigger_words = fill_all(&multi_trigger.triggers);
+    let negative_words = fill_all(&multi_trigger.negative);
+    if words_match(&multi_trigger.options, tokens, &trigger_words)
+        && !negative_words.iter().any(|word| tokens.contains(&word.as_str())) {
+        Some(trigger_words.len())
+    } else {
+        None
+    }
+}
+
+// a multi word trigger with every word known up front, as ids into TriggerMatcher::words
+struct CompiledTrigger {
+    distinct: usize,
+    negative: Vec<usize>,
+}
+
+// every way of deleting up to depth chars from a word, the word itself included. the same one can
+// come up more than once, "aab" is "ab" twice
+fn each_deletion(word: &str, depth: usize, visit: &mut impl FnMut(&str)) {
+    visit(word);
+    if depth == 0 {
+        return;
+    }
+    let mut variant = String::with_capacity(word.len());
+    for (i, c) in word.char_indices() {
+        variant.clear();
+        variant.push_str(&word[..i]);
+        variant.push_str(&word[i + c.len_utf8()..]);
+        each_deletion(&variant, depth - 1, visit);
+    }
+}
+
+// finds the words within their allowed typos of a token without comparing it to all of them. each
+// word is stored under every way of deleting up to its allowed typos from it, and a token that close
+// to it shares one of those, so only the few words it shares one with get their distance worked out
+#[derive(Default)]
+struct FuzzyIndex {
+    // each word and how many typos it allows
+    words: Vec<(Arc<str>, usize)>,
+    ids: HashMap<Arc<str>, usize>,
+    deletions: HashMap<String, Vec<usize>>,
+    // the most any word allows, which is how deep a token's deletions have to go
+    max_typos: usize,
+}
+
+impl FuzzyIndex {
+    // allowed is @fuzzy's count, the word's length can lower it. None when the word has to be exact
+    fn insert(&mut self, word: &Arc<str>, allowed: usize) -> Option<usize> {
+        let allowed = allowed.min(word.chars().count() / LETTERS_PER_TYPO);
+        if allowed == 0 {
+            return None;
+        }
+        let id = match self.ids.get(word) {
+            Some(id) if self.words[*id].1 >= allowed => return Some(*id),
+            Some(id) => *id,
+            None => {
+                self.words.push((word.clone(), 0));
+                self.ids.insert(word.clone(), self.words.len() - 1);
+                self.words.len() - 1
+            }
+        };
+        self.words[id].1 = allowed;
+        self.max_typos = self.max_typos.max(allowed);
+        each_deletion(word, allowed, &mut |variant| {
+            let ids = self.deletions.entry(String::from(variant)).or_default();
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        });
+        Some(id)
+    }
+
+    // the words a token is close enough to, as (id, typos)
+    fn lookup(&self, token: &str) -> Vec<(usize, usize)> {
+        if self.words.is_empty() || token.chars().count() + self.max_typos < LETTERS_PER_TYPO {
+            return Vec::new();
+        }
+        let mut ids = Vec::new();
+        each_deletion(token, self.max_typos, &mut |variant| {
+            if let Some(found) = self.deletions.get(variant) {
+                ids.extend_from_slice(found);
+            }
+        });
+        ids.sort_unstable();
+        ids.dedup();
+        ids.into_iter()
+            .filter_map(|id| {
+                let (word, allowed) = &self.words[id];
+                Some((id, typos(token, word, *allowed)?))
+            })
+            .collect()
+    }
+}
+
+// a multi word trigger with @fuzzy, a candidate once every one of its distinct words is close enough
+struct FuzzyTrigger {
+    index: usize,
+    distinct: usize,
+}
+
+// everything about the triggers that can be worked out once when they're loaded, so a message only
+// costs a lookup per token instead of a pass over every trigger
+pub struct TriggerMatcher {
+    // every word of every compiled multi word trigger
+    words: HashMap<Arc<str>, usize>,
+    // word id to the multi word triggers that need it
+    by_word: Vec<Vec<usize>>,
+    // by multi word trigger index, None for the ones that have to be checked one by one
+    compiled: Vec<Option<CompiledTrigger>>,
+    // multi word triggers with placeholders, which can't be looked up by word
+    uncompiled: Vec<usize>,
+    // multi word triggers with @fuzzy, only checked one by one once every word is close enough
+    fuzzy_multi: Vec<FuzzyTrigger>,
+    // the words of those that allow typos
+    fuzzy_words: FuzzyIndex,
+    // word id to the fuzzy_multi triggers that need it exactly, because it's too short for typos
+    fuzzy_by_word: Vec<Vec<usize>>,
+    // fuzzy word id to the fuzzy_multi triggers that need it, with the typos each allows
+    fuzzy_by_near: Vec<Vec<(usize, usize)>>,
+    // the single word triggers with @fuzzy, looked up when a word has no exact match
+    fuzzy: FuzzyIndex,
+    regexes: RegexSet,
+}
+
+impl TriggerMatcher {
+    pub fn new(triggers: &Map, multi_triggers: &[MultiTrigger], regex_triggers: &[RegexTrigger]) -> anyhow::Result<Self> {
+        let mut words = HashMap::new();
+        let mut by_word: Vec<Vec<usize>> = Vec::new();
+        let mut compiled = Vec::new();
+        let mut uncompiled = Vec::new();
+        let mut fuzzy_multi = Vec::new();
+        let mut fuzzy_words = FuzzyIndex::default();
+        let mut fuzzy_by_word: Vec<Vec<usize>> = Vec::new();
+        let mut fuzzy_by_near: Vec<Vec<(usize, usize)>> = Vec::new();
+        for (index, multi_trigger) in multi_triggers.iter().enumerate() {
+            let mut id = |word: &Arc<str>| -> usize {
+                let next = words.len();
+                *words.entry(word.clone()).or_insert(next)
+            };
+
+            let all_words = multi_trigger.triggers.iter().chain(&multi_trigger.negative);
+            if all_words.clone().any(|word| word.contains('{')) {
+                compiled.push(None);
+                uncompiled.push(index);
+                continue;
+            }
+            if let Some(allowed) = multi_trigger.options.fuzzy {
+                let position = fuzzy_multi.len();
+                let distinct: HashSet<&Arc<str>> = multi_trigger.triggers.iter().collect();
+                for word in &distinct {
+                    match fuzzy_words.insert(word, allowed) {
+                        Some(near) => {
+                            fuzzy_by_near.resize(fuzzy_words.words.len(), Vec::new());
+                            fuzzy_by_near[near].push((position, allowed.min(word.chars().count() / LETTERS_PER_TYPO)));
+                        }
+                        None => {
+                            let word = id(word);
+                            fuzzy_by_word.resize(fuzzy_by_word.len().max(word + 1), Vec::new());
+                            fuzzy_by_word[word].push(position);
+                        }
+                    }
+                }
+                compiled.push(None);
+                fuzzy_multi.push(FuzzyTrigger { index, distinct: distinct.len() });
+                continue;
+            }
+
+            let positive: HashSet<usize> = multi_trigger.triggers.iter().map(&mut id).collect();
+            let negative = multi_trigger.negative.iter().map(&mut id).collect();
+            by_word.resize(words.len(), Vec::new());
+            for word in &positive {
+                by_word[*word].push(index);
+            }
+            compiled.push(Some(CompiledTrigger {
+                distinct: positive.len(),
+                negative,
+            }));
+        }
+
+        // words are shared, so every id needs a slot in both
+        by_word.resize(words.len(), Vec::new());
+        fuzzy_by_word.resize(words.len(), Vec::new());
+
+        // the map's order is random, sorting keeps ties between fuzzy matches repeatable
+        let mut fuzzy_keys: Vec<(&Arc<str>, usize)> = triggers.iter()
+            .filter_map(|(key, entry)| Some((key, entry.options.fuzzy?)))
+            .collect();
+        fuzzy_keys.sort();
+        let mut fuzzy = FuzzyIndex::default();
+        for (key, allowed) in fuzzy_keys {
+            fuzzy.insert(key, allowed);
+        }
+
+        // case insensitive the same way each regex is, see compile_regex
+        let regexes = RegexSetBuilder::new(regex_triggers.iter().map(|regex_trigger| regex_trigger.regex.as_str()))
+            .case_insensitive(true)
+            .build()?;
+
+        Ok(TriggerMatcher {
+            words,
+            by_word,
+            compiled,
+            uncompiled,
+            fuzzy_multi,
+            fuzzy_words,
+            fuzzy_by_word,
+            fuzzy_by_near,
+            fuzzy,
+            regexes,
+        })
+    }
+
+    // the closest @fuzzy key to a token that had no exact match
+    pub fn closest_fuzzy(&self, token: &str) -> Option<&str> {
+        // ids go in sorted key order, so the lowest id wins a tie
+        self.fuzzy.lookup(token).into_iter()
+            .min_by_key(|(id, typos)| (*typos, *id))
+            .map(|(id, _)| &*self.fuzzy.words[id].0)
+    }
+
+    // the multi word triggers that match, as (index, matched words), in file order
+    pub fn multi_matches(&self, multi_triggers: &[MultiTrigger], tokens: &[&str], fill: &impl Fn(&str) -> Vec<String>) -> Vec<(usize, usize)> {
+        let present: HashSet<usize> = tokens.iter().filter_map(|token| self.words.get(*token).copied()).collect();
+
+        // a trigger is a candidate once every one of its words has shown up
+        let mut found: HashMap<usize, usize> = HashMap::new();
+        for word in &present {
+            for index in &self.by_word[*word] {
+                *found.entry(*index).or_default() += 1;
+            }
+        }
+
+        let mut matches: Vec<(usize, usize)> = found.into_iter()
+            .filter_map(|(index, count)| {
+                let compiled = self.compiled[index].as_ref()?;
+                if count < compiled.distinct { return None; }
+                if compiled.negative.iter().any(|word| present.contains(word)) { return None; }
+                // the order and distance rules only need checking on the few that get this far
+                let multi_trigger = &multi_triggers[index];
+                if !words_match(&multi_trigger.options, tokens, &multi_trigger.triggers) { return None; }
+                Some((index, multi_trigger.triggers.len()))
+            })
+            .collect();
+
+        // the fewest typos each fuzzy word turned up with, once per distinct token
+        let mut near: HashMap<usize, usize> = HashMap::new();
+        if !self.fuzzy_multi.is_empty() {
+            for token in tokens.iter().collect::<HashSet<_>>() {
+                for (word, typos) in self.fuzzy_words.lookup(token) {
+                    let fewest = near.entry(word).or_insert(typos);
+                    *fewest = typos.min(*fewest);
+                }
+            }
+        }
+        let mut found: HashMap<usize, usize> = HashMap::new();
+        for position in present.iter().flat_map(|word| &self.fuzzy_by_word[*word]) {
+            *found.entry(*position).or_default() += 1;
+        }
+        for (word, typos) in &near {
+            for (position, allowed) in &self.fuzzy_by_near[*word] {
+                if typos <= allowed {
+                    *found.entry(*position).or_default() += 1;
+                }
+            }
+        }
+        let fuzzy = found.into_iter()
+            .map(|(position, count)| (&self.fuzzy_multi[position], count))
+            .filter(|(fuzzy_trigger, count)| *count == fuzzy_trigger.distinct)
+            .map(|(fuzzy_trigger, _)| &fuzzy_trigger.index);
+        for index in self.uncompiled.iter().chain(fuzzy) {
+            if let Some(words) = multi_trigger_matches(&multi_triggers[*index], tokens, fill) {
+                matches.push((*index, words));
+            }
+        }
+        matches.sort();
+        matches
+    }
+
+    // indexes of the regex triggers that match, in file order
+    pub fn regex_matches(&self, text: &str) -> Vec<usize> {
+        self.regexes.matches(text).into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::ListsMaps;
+
+    #[test]
+    fn one_typo_agrees_with_osa_distance() {
+        // every string of up to 4 of these against every other
+        let mut words = vec![String::new()];
+        for _ in 0..4 {
+            let longer: Vec<String> = words.iter().flat_map(|word| ['a', 'b', 'é'].map(|c| format!("{}{}", word, c))).collect();
+            words.extend(longer);
+        }
+        words.sort();
+        words.dedup();
+        for token in &words {
+            for key in &words {
+                let expected = Some(strsim::osa_distance(token, key)).filter(|typos| *typos <= 1);
+                assert_eq!(one_typo(token, key), expected, "'{}' '{}'", token, key);
+            }
+        }
+    }
+
+    fn load() -> ListsMaps {
+        let lists = vec![
+            (String::from("passive_advice"), String::from("advice\n")),
+            (String::from("questions"), String::from("question?\n")),
+            (String::from("languages"), String::from("swift\nlisp\nscala\npython\njavascript\n")),
+            (String::from("games"), String::from("doom\ndota\npersona\ndark souls\nhollow knight\n")),
+        ];
+        ListsMaps::parse(&lists, "[languages @fuzzy=lang\n[games @fuzzy:2=game\n", "", "").unwrap()
+    }
+
+    #[test]
+    fn common_words_arent_typos_of_short_keys() {
+        let lm = load();
+        for word in ["shift", "data", "list", "scale", "personal", "door", "near"] {
+            assert_eq!(lm.matcher.closest_fuzzy(word), None, "'{}' matched", word);
+        }
+    }
+
+    #[test]
+    fn long_keys_allow_a_typo_per_six_letters() {
+        let lm = load();
+        assert_eq!(lm.matcher.closest_fuzzy("pyhton"), Some("python"));
+        assert_eq!(lm.matcher.closest_fuzzy("javscript"), Some("javascript"));
+        // @fuzzy:2 is only the most, "persona" is long enough for one
+        assert_eq!(lm.matcher.closest_fuzzy("persnoa"), Some("persona"));
+        assert_eq!(lm.matcher.closest_fuzzy("prsnoa"), None);
+    }
+
+    // the keys of the multi word triggers that match text, split on spaces, from alice
+    fn matches(lm: &ListsMaps, text: &str) -> Vec<String> {
+        let tokens: Vec<&str> = text.split(' ').collect();
+        lm.matcher.multi_matches(&lm.multi_triggers, &tokens, &|word: &str| vec![word.replace("{user}", "alice")])
+            .into_iter()
+            .map(|(index, _)| lm.multi_triggers[index].key.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn fuzzy_multi_word_triggers_get_past_the_prefilter() {
+        let lm = load();
+        // "hollow" and "knight" both allow a typo, "souls" and "dark" have to be exact
+        assert_eq!(matches(&lm, "i love holow knihgt"), ["hollow knight"]);
+        assert_eq!(matches(&lm, "dark souls is hard"), ["dark souls"]);
+        assert!(matches(&lm, "drak souls is hard").is_empty());
+    }
+
+    #[test]
+    fn match_modes() {
+        let lists = vec![
+            (String::from("passive_advice"), String::from("advice\n")),
+            (String::from("questions"), String::from("question?\n")),
+        ];
+        let triggers = "\
+hello world @ordered=a
+rust good @near:3=b
+your mom=c
+one two three @near:2=d
+";
+        let lm = ListsMaps::parse(&lists, triggers, "", "").unwrap();
+
+        assert_eq!(matches(&lm, "well hello world"), ["hello world"]);
+        assert!(matches(&lm, "world hello").is_empty());
+        assert!(matches(&lm, "hello big world").is_empty());
+
+        assert_eq!(matches(&lm, "rust is good"), ["rust good"]);
+        assert_eq!(matches(&lm, "good old rust"), ["rust good"]);
+        assert_eq!(matches(&lm, "rust good"), ["rust good"]);
+        assert!(matches(&lm, "rust is very good").is_empty());
+
+        // words are compared whole, not as prefixes
+        assert_eq!(matches(&lm, "that was your mom"), ["your mom"]);
+        assert!(matches(&lm, "wait your moment").is_empty());
+        assert!(matches(&lm, "yours mom").is_empty());
+
+        // three words never fit in a stretch of two, check warns about it
+        assert!(matches(&lm, "one two three").is_empty());
+    }
+
+    #[test]
+    fn long_phrases_and_negative_words() {
+        let lists = vec![
+            (String::from("passive_advice"), String::from("advice\n")),
+            (String::from("questions"), String::from("question?\n")),
+        ];
+        // the first two are looked up by word, the rest have placeholders and are checked one by one
+        let triggers = "\
+i really want to learn rust today=a
+learn rust -java=b
+hey {user} how are you doing=c
+thanks {user} -not=d
+nice one -{user}=e
+";
+        let lm = ListsMaps::parse(&lists, triggers, "", "").unwrap();
+
+        assert_eq!(matches(&lm, "so i really want to learn rust today ok"), ["i really want to learn rust today", "learn rust -java"]);
+        assert_eq!(matches(&lm, "i want to learn rust today"), ["learn rust -java"]);
+        assert!(matches(&lm, "learn java and rust").is_empty());
+
+        assert_eq!(matches(&lm, "hey alice how are you doing"), ["hey {user} how are you doing"]);
+        assert_eq!(matches(&lm, "how are you doing hey alice"), ["hey {user} how are you doing"]);
+        assert!(matches(&lm, "hey bob how are you doing").is_empty());
+        assert!(matches(&lm, "hey alice how are you").is_empty());
+
+        assert_eq!(matches(&lm, "thanks alice"), ["thanks {user} -not"]);
+        assert!(matches(&lm, "not thanks alice").is_empty());
+        assert_eq!(matches(&lm, "nice one"), ["nice one -{user}"]);
+        assert!(matches(&lm, "nice one alice").is_empty());
+    }
+}
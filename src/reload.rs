@@ -0,0 +1,68 @@
+use crate::data::{ config_dir, data_dir };
+use crate::env::Env;
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+use std::time::{ Duration, SystemTime };
+
+pub const RELOAD_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// polls the modified times of everything in the data and config dirs
+pub struct DataWatcher {
+    files: HashMap<PathBuf, SystemTime>,
+    next_poll: SystemTime,
+}
+
+fn snapshot() -> anyhow::Result<HashMap<PathBuf, SystemTime>> {
+    let mut files = HashMap::new();
+    for dir in [data_dir()?, config_dir()?] {
+        for file in std::fs::read_dir(dir)?.filter_map(|file| file.ok()) {
+            if let Ok(modified) = file.metadata().and_then(|metadata| metadata.modified()) {
+                files.insert(file.path(), modified);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+impl DataWatcher {
+    pub fn new(env: &Env) -> anyhow::Result<Self> {
+        Ok(DataWatcher {
+            files: snapshot()?,
+            next_poll: env.now() + RELOAD_POLL_INTERVAL,
+        })
+    }
+
+    // returns the names of files that were added, removed or modified since the last poll
+    pub fn poll(&mut self, env: &Env) -> Option<Vec<String>> {
+        if env.now() < self.next_poll {
+            return None;
+        }
+        self.next_poll = env.now() + RELOAD_POLL_INTERVAL;
+
+        let files = match snapshot() {
+            Ok(files) => files,
+            Err(e) => {
+                println!("failed to check data files for changes {:?}", e);
+                return None;
+            }
+        };
+
+        let mut changed: Vec<String> = files.iter()
+            .filter(|(path, modified)| self.files.get(*path) != Some(modified))
+            .chain(self.files.iter().filter(|(path, _)| !files.contains_key(*path)))
+            .map(|(path, _)| file_name(path))
+            .collect();
+        self.files = files;
+
+        if changed.is_empty() {
+            None
+        } else {
+            changed.sort();
+            Some(changed)
+        }
+    }
+}
@@ -0,0 +1,402 @@
+use crate::backend::ChatBackend;
+use crate::data::{ load_file, user_dir };
+use crate::env::Env;
+use rand::Rng;
+use serde::{ Serialize, Deserialize };
+use std::collections::{ HashMap, HashSet };
+use std::fs::File;
+use std::hash::{ Hash, Hasher };
+use std::io::prelude::*;
+use std::time::{ Duration, SystemTime };
+use strum::*;
+
+pub const PASSIVE_ADVICE_INTERVAL: Duration = Duration::from_secs(60 * 60 * 3); // 3h
+pub const BACKOFF_ADVICE_INTERVAL: Duration = Duration::from_secs(60 * 60 * 24); // 24h
+
+pub const PASSIVE_MESSAGE_RANGE: MinMax::<Duration> = MinMax::<Duration>::new( Duration::from_secs(600), Duration::from_secs(800) ); 
+
+pub const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+pub type ListHistory = HashMap<String, Vec<String>>;
+
+#[derive(Deserialize, Serialize)]
+pub struct MinMax<T> {
+    pub min: T,
+    pub max: T,
+}
+
+impl<T> MinMax<T> { 
+    pub const fn new(min: T, max: T) -> Self {
+        MinMax {
+            min,
+            max
+        }
+    }
+}
+
+#[derive(Display, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mood {
+    #[strum(to_string = "normal")]
+    Normal,
+    #[strum(to_string = "agitated")]
+    Backoff,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChannelState {
+    pub channel_name: String,
+    pub dedup_message: bool, 
+    pub direct_message: bool,
+    pub last_advice: SystemTime,
+    pub last_message: SystemTime,
+    pub mood: Mood,
+    pub next_advice: Duration,
+    pub next_message: MinMax<Duration>,
+    pub off_topic: Option<SystemTime>,
+    pub current_topic: Option<String>,
+    pub total_off_topic: Duration,
+    // when each trigger with a cooldown last fired
+    #[serde(default)]
+    pub trigger_cooldowns: HashMap<String, SystemTime>,
+    // percent scaling every trigger's chance to answer in this channel, None is 100
+    #[serde(default)]
+    pub chance: Option<u32>,
+    // per trigger chances set in this channel, these replace the data files and aren't scaled
+    #[serde(default)]
+    pub trigger_chances: HashMap<String, u32>,
+    // recent picks from the lists that avoid repeats, by list name, so a restart doesn't reset them
+    #[serde(default)]
+    pub list_history: ListHistory,
+}
+
+impl ChannelState { 
+    pub fn new(channel_name: &str, env: &Env) -> Self {
+        Self { 
+            direct_message: false,
+            channel_name: String::from(channel_name),
+            dedup_message: false,
+            last_advice: env.now(), 
+            last_message: env.now(),
+            mood: Mood::Normal, 
+            next_advice: PASSIVE_ADVICE_INTERVAL, 
+            next_message: PASSIVE_MESSAGE_RANGE,
+            off_topic: None,
+            current_topic: None,
+            total_off_topic: Duration::new(0, 0),
+            trigger_cooldowns: HashMap::new(),
+            chance: None,
+            trigger_chances: HashMap::new(),
+            list_history: HashMap::new(),
+        } 
+    }
+
+    pub fn set_mood(&mut self, mood: Mood) {
+        self.mood = mood;
+    }
+
+    // rolls a cooldown between next_message.min and next_message.max, and checks if it has passed
+    pub fn cooldown_elapsed(&self, env: &mut Env) -> bool {
+        let next_message = if self.next_message.min < self.next_message.max {
+            env.rng.gen_range(self.next_message.min..self.next_message.max)
+        } else {
+            self.next_message.min
+        };
+        self.direct_message || self.last_message + next_message < env.now()
+    }
+
+    pub fn trigger_ready(&self, trigger: &str, cooldown: Option<Duration>, env: &Env) -> bool {
+        match (cooldown, self.trigger_cooldowns.get(trigger)) {
+            (Some(cooldown), Some(last)) => *last + cooldown < env.now(),
+            _ => true,
+        }
+    }
+
+    // only triggers with a cooldown are remembered, so the map doesn't grow with every trigger
+    pub fn trigger_fired(&mut self, trigger: &str, cooldown: Option<Duration>, env: &Env) {
+        if cooldown.is_some() {
+            self.trigger_cooldowns.insert(String::from(trigger), env.now());
+        }
+    }
+
+    // rolls whether a trigger or command answers, a chance set for it in the channel replaces its
+    // @chance. the rng is left alone when it always does
+    pub fn roll_chance(&self, env: &mut Env, trigger: &str, chance: Option<u32>) -> bool {
+        let chance = self.trigger_chances.get(trigger).copied().unwrap_or(chance.unwrap_or(100));
+        chance >= 100 || env.rng.gen_range(0..100) < chance
+    }
+
+    // the channel wide "CONFIG chance" is for triggers only, command text ignores it
+    pub fn roll_trigger_chance(&self, env: &mut Env, trigger: &str, chance: Option<u32>) -> bool {
+        let chance = chance.unwrap_or(100) * self.chance.unwrap_or(100) / 100;
+        self.roll_chance(env, trigger, Some(chance))
+    }
+
+    pub fn advice_due(&self, env: &Env) -> bool {
+        self.last_advice + self.next_advice < env.now()
+    }
+
+    // returns how long the channel has been off topic, or starts the timer if it wasn't already
+    pub fn start_off_topic(&mut self, env: &Env) -> Option<Duration> {
+        match self.off_topic {
+            Some(stamp) => Some(env.now().duration_since(stamp).unwrap_or_default()),
+            None => {
+                self.off_topic = Some(env.now());
+                None
+            }
+        }
+    }
+
+    // stops the off topic timer, returning how long it ran for
+    pub fn end_off_topic(&mut self, env: &Env) -> Option<Duration> {
+        let start = self.off_topic.take()?;
+        let duration = env.now().duration_since(start).unwrap_or_default();
+        self.total_off_topic += duration;
+        Some(duration)
+    }
+
+    // returns whether the message was sent
+    pub async fn send_message(&mut self, env: &mut Env, backend: &mut impl ChatBackend, msg: &str) -> bool {
+        if self.cooldown_elapsed(env) {
+            self.force_send_message(env, backend, msg).await;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub async fn force_send_message(&mut self, env: &Env, backend: &mut impl ChatBackend, msg: &str) {
+        if let Err(e) = backend.send_message(&self.channel_name, msg).await {
+            println!("failed to send message to {}: {:?}", self.channel_name, e);
+        }
+
+        self.dedup_message = true;
+        self.last_advice = env.now();
+        self.last_message = env.now();
+        self.direct_message = false;
+    }
+}
+
+pub struct MetaState
+{
+    pub next_state_save: SystemTime,
+    pub persist: bool,
+    pub hot_reload: bool,
+}
+
+impl MetaState
+{
+    pub fn new(env: &Env) -> Self
+    {
+        MetaState {
+            next_state_save: env.now() + STATE_SAVE_INTERVAL,
+            persist: true,
+            hot_reload: true,
+        }
+    }
+
+    pub fn in_memory(env: &Env) -> Self
+    {
+        MetaState {
+            persist: false,
+            ..MetaState::new(env)
+        }
+    }
+
+    pub fn clean_temp_files() -> anyhow::Result<()>
+    {
+        for dir_entry in std::fs::read_dir(user_dir()?)? {
+            let path = dir_entry?.path();
+            let temp_ext = std::ffi::OsStr::new("temp");
+            if path.extension().filter(|ext| ext == &temp_ext).is_some() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn try_write_state(&mut self, env: &mut Env, state: &State) -> anyhow::Result<()>
+    {
+        if self.persist && self.next_state_save < env.now() {
+            let mut temp_file = user_dir()?;
+            temp_file.push(format!("{}-state.json.temp", env.rng.gen::<u32>()));
+            let serialized = serde_json::to_string(state);
+            std::fs::create_dir_all(temp_file.parent().unwrap()).unwrap();
+            let mut file = File::options()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&temp_file)
+                .unwrap();
+
+            file.write_all(serialized.unwrap().as_bytes())?;
+
+            let mut state_file_name = user_dir()?;
+            state_file_name.push("state.json");
+            std::fs::create_dir_all(state_file_name.parent().unwrap()).unwrap();
+            std::fs::rename(temp_file, state_file_name)?;
+            self.next_state_save = env.now() + STATE_SAVE_INTERVAL;
+        }
+        Ok(())
+    }
+
+    pub fn try_read_state(channels: Vec<&str>, env: &Env) -> State
+    {
+        let mut state_file_name = user_dir().unwrap();
+        state_file_name.push("state.json");
+
+        let contents = match load_file(&state_file_name) {
+            Ok(contents) => contents,
+            Err(_err) => return State::new(channels, env),
+        };
+        
+        // assume we want to crash if file exists but deserialization fails
+        State::merge(channels, serde_json::from_str(&contents).unwrap(), env)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct State {
+    // TODO: improve. hash is from channel name... just don't want to allocate every query...
+    pub channels: HashMap<u64, ChannelState>,
+    pub ignores: HashSet<String>,
+    // respond to every message, only used when trying things out offline
+    #[serde(skip)]
+    pub ignore_cooldowns: bool,
+}
+
+impl State {
+    pub fn new(channels: Vec<&str>, env: &Env) -> Self
+    {
+        println!("creating new state");
+        let chans = channels
+            .iter()
+            .map(|&chan| { 
+            (
+                State::chash(chan), 
+                ChannelState::new(chan, env),
+            ) } )
+            .collect();
+
+        State {
+            channels: chans,
+            ignores: HashSet::new(),
+            ignore_cooldowns: false,
+        }
+    }
+
+    pub fn chash(channel_name: &str) -> u64 {
+        // TODO ensure the hash is stable for io
+        let mut s = std::collections::hash_map::DefaultHasher::new();
+        channel_name.hash(&mut s);
+        s.finish()
+    }
+
+    pub fn merge(channels: Vec<&str>, mut state: State, env: &Env) -> State
+    {
+        for channel in channels {
+            state.channels.entry(Self::chash(channel)).or_insert_with(|| ChannelState::new(channel, env));
+        }
+        state
+    }
+
+    pub fn set_mood(&mut self, channel: &str, mood: Mood) {
+        if let Some( channel_state ) = self.channels.get_mut(&State::chash(channel)) {
+            channel_state.mood = mood;
+        }
+    } 
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::ManualClock;
+    use std::time::UNIX_EPOCH;
+
+    fn at(clock: &ManualClock, secs: u64) {
+        clock.set(UNIX_EPOCH + Duration::from_secs(secs));
+    }
+
+    #[test]
+    fn cooldown_elapses_after_the_message_range() {
+        let (mut env, clock) = Env::manual(1);
+        let mut cstate = ChannelState::new("chan", &env);
+
+        at(&clock, PASSIVE_MESSAGE_RANGE.min.as_secs() - 1);
+        assert!(!cstate.cooldown_elapsed(&mut env));
+        at(&clock, PASSIVE_MESSAGE_RANGE.max.as_secs() + 1);
+        assert!(cstate.cooldown_elapsed(&mut env));
+
+        // being talked to skips the wait
+        at(&clock, 1);
+        cstate.direct_message = true;
+        assert!(cstate.cooldown_elapsed(&mut env));
+    }
+
+    #[test]
+    fn trigger_cooldowns() {
+        let (env, clock) = Env::manual(1);
+        let mut cstate = ChannelState::new("chan", &env);
+        let cooldown = Some(Duration::from_secs(300));
+
+        at(&clock, 1000);
+        assert!(cstate.trigger_ready("mom", cooldown, &env));
+        cstate.trigger_fired("mom", cooldown, &env);
+        at(&clock, 1299);
+        assert!(!cstate.trigger_ready("mom", cooldown, &env));
+        assert!(cstate.trigger_ready("dad", cooldown, &env));
+        at(&clock, 1301);
+        assert!(cstate.trigger_ready("mom", cooldown, &env));
+
+        // triggers without a cooldown aren't remembered
+        cstate.trigger_fired("hello", None, &env);
+        assert!(!cstate.trigger_cooldowns.contains_key("hello"));
+        assert!(cstate.trigger_ready("hello", None, &env));
+    }
+
+    #[test]
+    fn chance_rolls() {
+        let (mut env, _clock) = Env::manual(1);
+        let (mut fresh, _) = Env::manual(1);
+        let mut cstate = ChannelState::new("chan", &env);
+
+        // always answering leaves the rng alone, so replays don't shift
+        assert!(cstate.roll_chance(&mut env, "hi", None));
+        assert!(cstate.roll_chance(&mut env, "hi", Some(100)));
+        assert_eq!(env.rng.next_u64(), fresh.rng.next_u64());
+
+        assert!(!cstate.roll_chance(&mut env, "hi", Some(0)));
+        let hits = (0..1000).filter(|_| cstate.roll_chance(&mut env, "hi", Some(50))).count();
+        assert!((400..600).contains(&hits), "{} of 1000", hits);
+
+        // the channel wide chance scales triggers, but not command text
+        cstate.chance = Some(0);
+        assert!(!cstate.roll_trigger_chance(&mut env, "hi", None));
+        assert!(cstate.roll_chance(&mut env, "!hi", None));
+
+        // a channel's per trigger chance replaces the one from the data files, and the channel's
+        cstate.trigger_chances.insert(String::from("hi"), 100);
+        assert!(cstate.roll_chance(&mut env, "hi", Some(0)));
+        assert!(cstate.roll_trigger_chance(&mut env, "hi", Some(0)));
+    }
+
+    #[test]
+    fn off_topic_timer() {
+        let (env, clock) = Env::manual(1);
+        let mut cstate = ChannelState::new("chan", &env);
+
+        assert_eq!(cstate.end_off_topic(&env), None);
+        at(&clock, 100);
+        assert_eq!(cstate.start_off_topic(&env), None);
+        at(&clock, 190);
+        assert_eq!(cstate.start_off_topic(&env), Some(Duration::from_secs(90)));
+        at(&clock, 200);
+        assert_eq!(cstate.end_off_topic(&env), Some(Duration::from_secs(100)));
+        assert_eq!(cstate.end_off_topic(&env), None);
+
+        at(&clock, 300);
+        cstate.start_off_topic(&env);
+        at(&clock, 350);
+        cstate.end_off_topic(&env);
+        assert_eq!(cstate.total_off_topic, Duration::from_secs(150));
+    }
+}
@@ -0,0 +1,468 @@
+use crate::data::{ ListsMaps, MapValue, Repeat };
+use crate::env::Env;
+use crate::state::{ ChannelState, ListHistory };
+use rand::Rng;
+use regex::Captures;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// how many lists deep a placeholder can go, {a} picking an entry with {b} in it is two
+pub const MAX_DEPTH: usize = 8;
+
+const ME: &str = "somewhatinaccurate"; // TODO get this from somewhere
+
+// what can go after a | in a placeholder, {foods|plural}
+pub const FILTERS: [&str; 5] = ["upper", "lower", "capitalize", "plural", "a"];
+
+// words that don't take the article their first letter suggests, by how they start. "uni" alone
+// would catch "uninstalled" too
+const AN_EXCEPTIONS: [&str; 4] = ["hour", "honest", "honor", "heir"];
+const A_EXCEPTIONS: [&str; 11] = ["unic", "unif", "unio", "uniq", "unis", "unit", "univ", "use", "usu", "uti", "eu"];
+// and whole words, "onerous" isn't said like "one"
+const A_WORDS: [&str; 2] = ["one", "once"];
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Placeholder<'a> {
+    // everything between the braces
+    pub raw: &'a str,
+    pub name: &'a str,
+    // {foods:a} keeps what it picked as {a}, for when the same pick is wanted again
+    pub binding: Option<&'a str>,
+    // applied in order, {foods|plural|upper}
+    pub filters: Vec<&'a str>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Part<'a> {
+    Text(&'a str),
+    Placeholder(Placeholder<'a>),
+}
+
+fn placeholder(raw: &str) -> Placeholder<'_> {
+    let mut filters = raw.split('|');
+    let source = filters.next().unwrap_or(raw);
+    let (name, binding) = match source.split_once(':') {
+        Some((name, binding)) => (name, Some(binding)),
+        None => (source, None),
+    };
+    Placeholder {
+        raw,
+        name,
+        binding,
+        filters: filters.collect(),
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// only the last word changes, "slice of pizza" is the exception we live with. the ending matches
+// the word's case, so it can come after |upper too
+fn plural(text: &str) -> String {
+    let word_start = text.rfind(' ').map_or(0, |space| space + 1);
+    let upper = text[word_start..].chars().any(char::is_alphabetic) && !text[word_start..].chars().any(char::is_lowercase);
+    let word = text[word_start..].to_lowercase();
+    let ending = if ["s", "x", "z", "ch", "sh"].iter().any(|end| word.ends_with(end)) {
+        "es"
+    } else if word.strip_suffix('y').is_some_and(|stem| !stem.is_empty() && !stem.ends_with(['a', 'e', 'i', 'o', 'u'])) {
+        "ies"
+    } else if !word.is_empty() {
+        "s"
+    } else {
+        ""
+    };
+    let mut result = String::from(text);
+    if ending == "ies" {
+        result.pop();
+    }
+    result.push_str(&if upper { ending.to_uppercase() } else { String::from(ending) });
+    result
+}
+
+fn article(text: &str) -> String {
+    let word = text.trim_start().to_lowercase();
+    let vowel = word.starts_with(['a', 'e', 'i', 'o', 'u']);
+    let first_word = word.split(|c: char| !c.is_alphanumeric()).next().unwrap_or("");
+    let an = if vowel {
+        !A_WORDS.contains(&first_word) && !A_EXCEPTIONS.iter().any(|exception| word.starts_with(exception))
+    } else {
+        AN_EXCEPTIONS.iter().any(|exception| word.starts_with(exception))
+    };
+    format!("{} {}", if an { "an" } else { "a" }, text)
+}
+
+fn filter(value: &str, name: &str) -> String {
+    match name {
+        "upper" => value.to_uppercase(),
+        "lower" => value.to_lowercase(),
+        "capitalize" => capitalize(value),
+        "plural" => plural(value),
+        "a" => article(value),
+        _ => {
+            println!("unknown template filter {}", name);
+            String::from(value)
+        }
+    }
+}
+
+// splits a template into text and {placeholders}. {{ and }} are a literal brace, and a brace
+// that doesn't make a placeholder is left as text
+pub fn parse(template: &str) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(brace) = rest.find(['{', '}']) {
+        if brace > 0 {
+            parts.push(Part::Text(&rest[..brace]));
+        }
+        rest = &rest[brace..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            parts.push(Part::Text(&rest[..1]));
+            rest = &rest[2..];
+            continue;
+        }
+        if rest.starts_with('{') {
+            if let Some(end) = rest[1..].find(['{', '}']).filter(|end| rest[1 + end..].starts_with('}')) {
+                parts.push(Part::Placeholder(placeholder(&rest[1..1 + end])));
+                rest = &rest[end + 2..];
+                continue;
+            }
+        }
+        parts.push(Part::Text(&rest[..1]));
+        rest = &rest[1..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest));
+    }
+    parts
+}
+
+pub fn placeholders(template: &str) -> impl Iterator<Item = Placeholder<'_>> {
+    parse(template).into_iter().filter_map(|part| match part {
+        Part::Placeholder(placeholder) => Some(placeholder),
+        Part::Text(_) => None,
+    })
+}
+
+// what the message being answered fills in, as opposed to the lists
+pub struct Context<'a> {
+    pub channel: &'a str,
+    pub user: &'a str,
+    pub trigger: &'a str,
+    pub captures: Option<&'a Captures<'a>>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(channel: &'a str, user: &'a str, trigger: &'a str) -> Self {
+        Context {
+            channel,
+            user,
+            trigger,
+            captures: None,
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&'a str> {
+        match name {
+            "trigger" => Some(self.trigger),
+            "user" => Some(self.user),
+            "channel" => Some(self.channel),
+            _ => {
+                // {1}, {2}.. are a regex trigger's capture groups, a group that didn't take part is empty
+                let captures = self.captures?;
+                let group = name.parse::<usize>().ok().filter(|group| *group < captures.len())?;
+                Some(captures.get(group).map_or("", |capture| capture.as_str()))
+            }
+        }
+    }
+}
+
+struct Expander<'p, 'c> {
+    pick: &'p mut dyn FnMut(&str) -> Option<Arc<str>>,
+    context: Option<&'p Context<'c>>,
+    // the lists being expanded right now, outermost first
+    lists: Vec<String>,
+    // from {list:name}, for the whole response, nested entries included
+    bindings: HashMap<String, String>,
+    result: String,
+}
+
+impl Expander<'_, '_> {
+    fn expand(&mut self, template: &str) {
+        for part in parse(template) {
+            match part {
+                Part::Text(text) => self.result.push_str(text),
+                Part::Placeholder(placeholder) => self.placeholder(&placeholder),
+            }
+        }
+    }
+
+    fn placeholder(&mut self, placeholder: &Placeholder) {
+        let start = self.result.len();
+        let filled = match placeholder.binding {
+            // every {foods:a} after the first is the same as {a}
+            Some(binding) if self.bindings.contains_key(binding) => {
+                self.result.push_str(&self.bindings[binding]);
+                true
+            }
+            Some(binding) => {
+                let filled = self.fill(placeholder.name, placeholder.raw);
+                // before the filters, so {a|upper} can still use it differently. nothing is kept
+                // when there was nothing to pick, {a} stays as written
+                if filled {
+                    self.bindings.insert(String::from(binding), self.result[start..].to_string());
+                }
+                filled
+            }
+            None => self.fill(placeholder.name, placeholder.raw),
+        };
+        if filled && !placeholder.filters.is_empty() {
+            let value = self.result.split_off(start);
+            let value = placeholder.filters.iter().fold(value, |value, name| filter(&value, name));
+            self.result.push_str(&value);
+        }
+    }
+
+    // false when there was nothing to fill it with
+    fn fill(&mut self, name: &str, raw: &str) -> bool {
+        let value = if name == "me" { Some(ME) } else { self.context.and_then(|context| context.get(name)) };
+        if let Some(value) = value {
+            // text from the message goes in as is, so nobody can type a placeholder into it
+            self.result.push_str(value);
+            return true;
+        }
+        if let Some(value) = self.bindings.get(name) {
+            self.result.push_str(value);
+            return true;
+        }
+
+        // cut off, it's dropped rather than showing a half expanded {name} in chat
+        if self.lists.iter().any(|list| list == name) {
+            println!("list {} includes itself through {}, leaving it out", name, self.lists.join(" -> "));
+            return false;
+        }
+        if self.lists.len() == MAX_DEPTH {
+            println!("lists nested deeper than {} at {}, leaving out {}", MAX_DEPTH, self.lists.join(" -> "), name);
+            return false;
+        }
+        if let Some(entry) = (self.pick)(name) {
+            self.lists.push(String::from(name));
+            self.expand(&entry);
+            self.lists.pop();
+            return true;
+        }
+        // nothing to fill it with, left the way it was written
+        self.result.push('{');
+        self.result.push_str(raw);
+        self.result.push('}');
+        false
+    }
+}
+
+fn render<'a>(template: Cow<'a, str>, pick: &mut dyn FnMut(&str) -> Option<Arc<str>>, context: Option<&Context>) -> Cow<'a, str> {
+    if !template.contains(['{', '}']) {
+        return template;
+    }
+    let mut expander = Expander {
+        pick,
+        context,
+        lists: Vec::new(),
+        bindings: HashMap::new(),
+        result: String::with_capacity(template.len()),
+    };
+    expander.expand(&template);
+    Cow::Owned(expander.result)
+}
+
+// a random entry of a list, going by the channel's history for lists that avoid repeats
+pub fn pick_from(lm: &ListsMaps, rng: &mut impl Rng, history: &mut ListHistory, name: &str) -> Option<Arc<str>> {
+    let list = lm.lists.get(name)?;
+    // only those lists get anything remembered
+    let recent = match list.repeat {
+        Repeat::Random => None,
+        _ => Some(history.entry(String::from(name)).or_default()),
+    };
+    list.pick(rng, recent).cloned()
+}
+
+// fills in {list} placeholders with a random entry, and any placeholders in that entry too
+pub fn substitute_random<'a>(lm: &ListsMaps, rng: &mut impl Rng, history: &mut ListHistory, message: &'a str) -> Cow<'a, str> {
+    render(Cow::Borrowed(message), &mut |name| pick_from(lm, rng, history, name), None)
+}
+
+// only {me}, which is the same everywhere, so keys can use it
+pub fn subst_global(message: Cow<'_, str>) -> Cow<'_, str> {
+    render(message, &mut |_| None, None)
+}
+
+pub fn subst_context<'a>(state: &ChannelState, user: &str, trigger: &str, message: Cow<'a, str>) -> Cow<'a, str> {
+    render(message, &mut |_| None, Some(&Context::new(&state.channel_name, user, trigger)))
+}
+
+pub fn make_response<'a>(lm: &'a ListsMaps, env: &mut Env, history: &mut ListHistory, context: &Context, map_value: &'a MapValue) -> Option<Cow<'a, str>> {
+    let mut pick = |name: &str| pick_from(lm, &mut env.rng, history, name);
+    match map_value {
+        MapValue::FileName(name) => {
+            println!("detected file {}", name);
+            if !lm.lists.contains_key(&**name) {
+                return None;
+            }
+            // the same as a value of just {name}, so an entry that picks its own list is caught
+            let mut expander = Expander {
+                pick: &mut pick,
+                context: Some(context),
+                lists: Vec::new(),
+                bindings: HashMap::new(),
+                result: String::new(),
+            };
+            // an empty list has nothing to say
+            if !expander.fill(name, name) {
+                return None;
+            }
+            Some(Cow::Owned(expander.result))
+        }
+        MapValue::Value(value) => {
+            println!("detected value {}", value);
+            Some(render(Cow::Borrowed(&**value), &mut pick, Some(context)))
+        }
+    }
+}
+
+pub fn make_response_message<'b>(state: &mut ChannelState, lm: &ListsMaps, env: &mut Env, user: &str, trigger: &str, msg: &'b str) -> Cow<'b, str> {
+    let context = Context::new(&state.channel_name, user, trigger);
+    render(Cow::Borrowed(msg), &mut |name| pick_from(lm, &mut env.rng, &mut state.list_history, name), Some(&context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    // every list has one entry, so what comes out doesn't depend on the rng
+    fn expand(lists: &[(&str, &str)], template: &str) -> String {
+        let lists: HashMap<&str, &str> = lists.iter().copied().collect();
+        let context = Context::new("chan", "viewer", "hi");
+        render(Cow::Borrowed(template), &mut |name| lists.get(name).map(|entry| Arc::from(*entry)), Some(&context)).into_owned()
+    }
+
+    #[test]
+    fn escapes_and_stray_braces() {
+        assert_eq!(expand(&[], "{{user}} is {user}"), "{user} is viewer");
+        assert_eq!(expand(&[], "}} and {{"), "} and {");
+        assert_eq!(expand(&[], "hi {user"), "hi {user");
+        assert_eq!(expand(&[], "a } b { c"), "a } b { c");
+        // unknown placeholders are left the way they were written
+        assert_eq!(expand(&[], "{nope} {user}"), "{nope} viewer");
+    }
+
+    #[test]
+    fn nested_lists() {
+        let lists = [("greeting", "hey {name}"), ("name", "{user}!")];
+        assert_eq!(expand(&lists, "{greeting}"), "hey viewer!");
+    }
+
+    #[test]
+    fn cycles_and_depth_are_cut_off() {
+        // the placeholder that would go around again is dropped
+        let lists = [("a", "a {b}"), ("b", "b {a}.")];
+        assert_eq!(expand(&lists, "{a}"), "a b .");
+
+        let names: Vec<String> = (0..10).map(|i| format!("l{}", i)).collect();
+        let entries: Vec<String> = (0..10).map(|i| format!("{}{{l{}}}", i, i + 1)).collect();
+        let lists: Vec<(&str, &str)> = names.iter().zip(&entries).map(|(name, entry)| (name.as_str(), entry.as_str())).collect();
+        assert_eq!(expand(&lists, "{l0}"), "01234567");
+    }
+
+    #[test]
+    fn an_empty_list_says_nothing() {
+        let lists = vec![
+            (String::from("passive_advice"), String::from("advice\n")),
+            (String::from("questions"), String::from("question?\n")),
+            (String::from("empty"), String::new()),
+        ];
+        let lm = ListsMaps::parse(&lists, "", "", "").unwrap();
+        let (mut env, _clock) = Env::manual(0);
+        let context = Context::new("chan", "viewer", "hi");
+        let value = MapValue::FileName(Arc::from("empty"));
+        assert_eq!(make_response(&lm, &mut env, &mut ListHistory::new(), &context, &value), None);
+    }
+
+    fn numbers() -> ListsMaps {
+        let lists = vec![
+            (String::from("passive_advice"), String::from("advice\n")),
+            (String::from("questions"), String::from("question?\n")),
+            (String::from("n"), (0..20).map(|i| format!("{}\n", i)).collect()),
+        ];
+        ListsMaps::parse(&lists, "", "", "").unwrap()
+    }
+
+    #[test]
+    fn repeated_placeholders_pick_independently() {
+        let lm = numbers();
+        let (mut env, _clock) = Env::manual(3);
+        let picked = substitute_random(&lm, &mut env.rng, &mut ListHistory::new(), "{n} {n} {n} {n} {n} {n}").into_owned();
+        let distinct: HashSet<&str> = picked.split(' ').collect();
+        assert!(distinct.len() > 1, "'{}'", picked);
+    }
+
+    #[test]
+    fn bindings_reuse_the_pick() {
+        let lm = numbers();
+        let (mut env, _clock) = Env::manual(3);
+        for _ in 0..20 {
+            let picked = substitute_random(&lm, &mut env.rng, &mut ListHistory::new(), "{n:x} {x} {n:x} {x}").into_owned();
+            let words: Vec<&str> = picked.split(' ').collect();
+            assert!(words.iter().all(|word| *word == words[0]), "'{}'", picked);
+        }
+
+        // a list that doesn't exist binds nothing
+        let picked = substitute_random(&lm, &mut env.rng, &mut ListHistory::new(), "{nope:x} then {x}");
+        assert_eq!(picked, "{nope:x} then {x}");
+    }
+
+    #[test]
+    fn filters_apply_in_order() {
+        let lists = [("food", "cherry"), ("drink", "lemon squash")];
+        assert_eq!(expand(&lists, "{food|plural|upper}"), "CHERRIES");
+        assert_eq!(expand(&lists, "{food|upper|plural}"), "CHERRIES");
+        assert_eq!(expand(&lists, "{food|a|upper} {food|upper|a}"), "A CHERRY a CHERRY");
+        assert_eq!(expand(&lists, "{drink|plural|capitalize}"), "Lemon squashes");
+        assert_eq!(expand(&lists, "{food:f|a} and {f|plural}"), "a cherry and cherries");
+        // an unknown filter is skipped, a placeholder that wasn't filled isn't filtered
+        assert_eq!(expand(&lists, "{food|nope} {nope|upper}"), "cherry {nope|upper}");
+    }
+
+    #[test]
+    fn articles() {
+        assert_eq!(article("apple"), "an apple");
+        assert_eq!(article("banana"), "a banana");
+        assert_eq!(article("hour"), "an hour");
+        assert_eq!(article("Honest mistake"), "an Honest mistake");
+        assert_eq!(article("unicorn"), "a unicorn");
+        assert_eq!(article("user"), "a user");
+        assert_eq!(article("one"), "a one");
+        assert_eq!(article("one-eyed cat"), "a one-eyed cat");
+        assert_eq!(article("umbrella"), "an umbrella");
+        assert_eq!(article("uninstalled game"), "an uninstalled game");
+        assert_eq!(article("union"), "a union");
+        assert_eq!(article("universe"), "a universe");
+        assert_eq!(article("onerous task"), "an onerous task");
+        assert_eq!(article("euro"), "a euro");
+    }
+
+    #[test]
+    fn plurals() {
+        assert_eq!(plural("box"), "boxes");
+        assert_eq!(plural("day"), "days");
+        assert_eq!(plural("pony"), "ponies");
+        assert_eq!(plural("slice of pizza"), "slice of pizzas");
+        assert_eq!(plural("BOX"), "BOXES");
+        assert_eq!(plural("big PONY"), "big PONIES");
+        assert_eq!(plural("Pony"), "Ponies");
+    }
+}
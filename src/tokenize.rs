@@ -0,0 +1,142 @@
+use std::ops::Range;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+// letters from other scripts that look just like latin ones, people use them to dodge filters
+const CONFUSABLES: [(char, char); 22] = [
+    ('а', 'a'), ('в', 'b'), ('е', 'e'), ('к', 'k'), ('м', 'm'), ('н', 'h'), ('о', 'o'), ('р', 'p'),
+    ('с', 'c'), ('т', 't'), ('у', 'y'), ('х', 'x'), ('і', 'i'), ('ј', 'j'), ('ѕ', 's'), ('ԁ', 'd'),
+    ('α', 'a'), ('ι', 'i'), ('κ', 'k'), ('ν', 'v'), ('ο', 'o'), ('ρ', 'p'),
+];
+
+// how many of the same letter in a row before they're squashed into one, "ruuuust" is "rust" but
+// "good" stays "good". only letters, "1000" stays "1000"
+const REPEAT_LIMIT: usize = 3;
+
+// a whitespace separated word of a message. raw is only lowercased, so keys like "c++" still
+// match, tokens are the word with punctuation stripped and unicode tricks undone
+#[derive(Debug)]
+pub struct Word {
+    pub raw: String,
+    pub tokens: Vec<String>,
+}
+
+fn unconfuse(c: char) -> char {
+    CONFUSABLES.iter()
+        .find(|(confusable, _)| *confusable == c)
+        .map_or(c, |(_, latin)| *latin)
+}
+
+fn squash_repeats(token: &str) -> String {
+    let chars: Vec<char> = token.chars().collect();
+    let mut result = String::with_capacity(token.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let run = chars[i..].iter().take_while(|c| **c == chars[i]).count();
+        let keep = if run >= REPEAT_LIMIT && chars[i].is_alphabetic() { 1 } else { run };
+        result.extend(std::iter::repeat_n(chars[i], keep));
+        i += run;
+    }
+    result
+}
+
+// splits a word into its normalized tokens. apostrophes are dropped so "i'm" is "im", any other
+// punctuation separates tokens
+pub fn normalize(word: &str) -> Vec<String> {
+    let cleaned: String = word.nfkd()
+        .filter(|c| !is_combining_mark(*c) && *c != '\'' && *c != '’')
+        .flat_map(char::to_lowercase)
+        // after lowercasing, the table only has the lowercase lookalikes
+        .map(unconfuse)
+        .collect();
+    cleaned.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(squash_repeats)
+        .collect()
+}
+
+// what a trigger key is stored as, so it compares equal to what tokenize makes of a message. keys
+// with punctuation, like "c++", are only lowercased, they're compared against the word as typed
+pub fn normalize_key(key: &str) -> String {
+    if key.chars().all(|c| c.is_alphanumeric() || c == '\'' || c == '’') {
+        normalize(key).concat()
+    } else {
+        key.to_lowercase()
+    }
+}
+
+// emotes are char ranges, from the message's emote tags. words that start inside one are skipped
+pub fn tokenize(text: &str, emotes: &[Range<usize>]) -> Vec<Word> {
+    let mut words = Vec::new();
+    let mut next_start = 0;
+    // splitting on every whitespace char, rather than runs of them, keeps count of where each
+    // word starts
+    for word in text.split(char::is_whitespace) {
+        let start = next_start;
+        next_start += word.chars().count() + 1;
+        if word.is_empty() { continue; }
+        if emotes.iter().any(|emote| emote.contains(&start)) { continue; }
+
+        words.push(Word {
+            raw: word.to_lowercase(),
+            tokens: normalize(word),
+        });
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookalikes_of_either_case() {
+        // cyrillic and greek, both cases
+        assert_eq!(normalize("РУСТ"), ["pyct"]);
+        assert_eq!(normalize("Јаvа"), ["java"]);
+        assert_eq!(normalize("ΚΑΡΟ"), ["kapo"]);
+        assert_eq!(normalize("ruuuust"), ["rust"]);
+    }
+
+    #[test]
+    fn only_letters_are_squashed() {
+        assert_eq!(normalize("gooood"), ["god"]);
+        assert_eq!(normalize("good"), ["good"]);
+        assert_eq!(normalize("1000"), ["1000"]);
+        assert_eq!(normalize("x1000yyy"), ["x1000y"]);
+    }
+
+    #[test]
+    fn punctuation() {
+        assert_eq!(normalize("rust!"), ["rust"]);
+        assert_eq!(normalize("rust!!!"), ["rust"]);
+        assert_eq!(normalize("I'm"), ["im"]);
+        assert_eq!(normalize("rust/go"), ["rust", "go"]);
+        assert_eq!(normalize("c++"), ["c"]);
+        assert!(normalize("!!!").is_empty());
+        assert_eq!(normalize_key("Rust"), "rust");
+        assert_eq!(normalize_key("I’m"), "im");
+        assert_eq!(normalize_key("C++"), "c++");
+
+        let words = tokenize("I like C++, and RUST!", &[]);
+        let raw: Vec<&str> = words.iter().map(|word| word.raw.as_str()).collect();
+        assert_eq!(raw, ["i", "like", "c++,", "and", "rust!"]);
+        assert_eq!(words[2].tokens, ["c"]);
+        assert_eq!(words[4].tokens, ["rust"]);
+    }
+
+    #[test]
+    fn words_in_emotes_are_skipped() {
+        let raw = |text: &str, emotes: &[Range<usize>]| -> Vec<String> {
+            tokenize(text, emotes).into_iter().map(|word| word.raw).collect()
+        };
+        // "Kappa" is chars 6..11, "PogChamp" 17..25
+        let text = "hello Kappa rust PogChamp";
+        assert_eq!(raw(text, &[]), ["hello", "kappa", "rust", "pogchamp"]);
+        assert_eq!(raw(text, &[6..11, 17..25]), ["hello", "rust"]);
+        // ranges are in chars, not bytes, and double spaces still count
+        assert_eq!(raw("héllo  Kappa rust Kappa", &[7..12, 18..23]), ["héllo", "rust"]);
+        // only where a word starts matters
+        assert_eq!(raw(text, &[8..14, 17..25]), ["hello", "kappa"]);
+    }
+}
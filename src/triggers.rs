@@ -0,0 +1,167 @@
+use crate::backend::{ ChatBackend, ChatMessage };
+use crate::data::{ ListsMaps, MapValue, TriggerOptions };
+use crate::env::Env;
+use crate::state::{ Mood, State };
+use crate::template::{ make_response, subst_context, Context };
+use crate::tokenize::{ normalize, tokenize };
+use regex::Captures;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::time::Duration;
+
+// leave words twitch says are emotes out of trigger matching, "Kappa" is not a conversation
+pub const IGNORE_EMOTES: bool = true;
+
+// how many triggers the bot answers in one message, the best scoring ones go first
+pub const MAX_RESPONSES: usize = 1;
+
+// something in the message that could be responded to
+struct Candidate<'a> {
+    kind: &'static str,
+    // what repeats are removed by and a channel's chance for it is set under
+    key: String,
+    // what the cooldown is tracked under, the list for keys from one
+    cooldown_key: String,
+    // what {trigger} is filled with
+    trigger: String,
+    value: &'a MapValue,
+    cooldown: Option<Duration>,
+    chance: Option<u32>,
+    priority: i32,
+    // how many words of the message the trigger matched, more specific triggers win ties
+    words: usize,
+    // after that, typo free matches win
+    exact: bool,
+    captures: Option<Captures<'a>>,
+}
+
+pub async fn handle_triggers(state: &mut State, lm: &ListsMaps, env: &mut Env, backend: &mut impl ChatBackend, msg: &ChatMessage) -> anyhow::Result<()> {
+    let channel = &msg.channel[..];
+    // trying things out offline shouldn't be slowed down by cooldowns
+    let ignore_cooldowns = state.ignore_cooldowns;
+    let cooldown = |options: &TriggerOptions| if ignore_cooldowns { None } else { options.cooldown };
+    if let Some( cstate ) = state.channels.get_mut(&State::chash(channel)) {
+        if cstate.mood == Mood::Normal && !state.ignores.contains(&msg.user) { 
+            let emotes: &[Range<usize>] = if IGNORE_EMOTES { &msg.emotes } else { &[] };
+            let words = tokenize(&msg.text, emotes);
+            let mut candidates = Vec::new();
+
+            for word in &words {
+                // the word as typed wins, so keys with punctuation like "c++" still match
+                let tokens = if lm.triggers.contains_key(word.raw.as_str()) {
+                    std::slice::from_ref(&word.raw)
+                } else {
+                    &word.tokens[..]
+                };
+                for token in tokens {
+                    let (kind, key) = if lm.triggers.contains_key(token.as_str()) {
+                        ("single", &token[..])
+                    } else if let Some(key) = lm.matcher.closest_fuzzy(token) {
+                        ("fuzzy", key)
+                    } else {
+                        continue;
+                    };
+                    let entry = &lm.triggers[key];
+                    candidates.push(Candidate {
+                        kind,
+                        key: String::from(key),
+                        cooldown_key: String::from(entry.options.cooldown_key.as_deref().unwrap_or(key)),
+                        trigger: String::from(key),
+                        value: &entry.value,
+                        cooldown: cooldown(&entry.options),
+                        chance: entry.options.chance,
+                        priority: entry.options.priority,
+                        words: 1,
+                        exact: kind != "fuzzy",
+                        captures: None,
+                    });
+                }
+            }
+
+            let tokens: Vec<&str> = words.iter()
+                .flat_map(|word| word.tokens.iter().map(|token| token.as_str()))
+                .collect();
+            // words with placeholders are normalized once they're filled in
+            let fill = |word: &str| normalize(&subst_context(cstate, &msg.user, "", Cow::Borrowed(word)));
+            for (index, words) in lm.matcher.multi_matches(&lm.multi_triggers, &tokens, &fill) {
+                let multi_trigger = &lm.multi_triggers[index];
+                candidates.push(Candidate {
+                    kind: "multi",
+                    key: String::from(&*multi_trigger.key),
+                    cooldown_key: String::from(multi_trigger.options.cooldown_key.as_deref().unwrap_or(&multi_trigger.key)),
+                    trigger: multi_trigger.triggers.join(" "),
+                    value: &multi_trigger.value,
+                    cooldown: cooldown(&multi_trigger.options),
+                    chance: multi_trigger.options.chance,
+                    priority: multi_trigger.options.priority,
+                    words,
+                    exact: multi_trigger.options.fuzzy.is_none(),
+                    captures: None,
+                });
+            }
+
+            // the regexes ignore case, so the captures keep the way it was written
+            for index in lm.matcher.regex_matches(&msg.text) {
+                let regex_trigger = &lm.regex_triggers[index];
+                if let Some(captures) = regex_trigger.regex.captures(&msg.text) {
+                    candidates.push(Candidate {
+                        kind: "regex",
+                        key: String::from(regex_trigger.regex.as_str()),
+                        cooldown_key: String::from(regex_trigger.regex.as_str()),
+                        trigger: String::from(&captures[0]),
+                        value: &regex_trigger.value,
+                        cooldown: cooldown(&regex_trigger.options),
+                        chance: regex_trigger.options.chance,
+                        priority: regex_trigger.options.priority,
+                        words: captures[0].split_whitespace().count().max(1),
+                        exact: true,
+                        captures: Some(captures),
+                    });
+                }
+            }
+
+            // the same trigger can show up more than once, "rust rust rust"
+            let mut seen = HashSet::new();
+            candidates.retain(|candidate| seen.insert(candidate.key.clone()));
+            // stable, so ties go to whatever came first in the message, then in the file
+            candidates.sort_by_key(|candidate| Reverse((candidate.priority, candidate.words, candidate.exact)));
+
+            if candidates.is_empty() {
+                return Ok(());
+            }
+            println!("trigger candidates, best first:");
+            for candidate in &candidates {
+                let ready = cstate.trigger_ready(&candidate.cooldown_key, candidate.cooldown, env);
+                println!("  {} '{}' priority {} words {}{}", candidate.kind, candidate.key, candidate.priority, candidate.words,
+                         if ready { "" } else { " (cooling down)" });
+            }
+
+            let mut responses = 0;
+            for candidate in &candidates {
+                if responses == MAX_RESPONSES { break; }
+                if !cstate.trigger_ready(&candidate.cooldown_key, candidate.cooldown, env) { continue; }
+                if !cstate.roll_trigger_chance(env, &candidate.key, candidate.chance) {
+                    println!("{} '{}' didn't roll its chance", candidate.kind, candidate.key);
+                    continue;
+                }
+
+                let context = Context {
+                    captures: candidate.captures.as_ref(),
+                    ..Context::new(&cstate.channel_name, &msg.user, &candidate.trigger)
+                };
+                if let Some(response) = make_response(lm, env, &mut cstate.list_history, &context, candidate.value) {
+                    println!("responding to {} '{}'", candidate.kind, candidate.key);
+                    if !cstate.send_message(env, backend, &response).await {
+                        // the channel is cooling down, nothing else will get through either
+                        break;
+                    }
+                    cstate.trigger_fired(&candidate.cooldown_key, candidate.cooldown, env);
+                    responses += 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,47 @@
+// `cynobot check` is meant for scripts and CI, so what it exits with matters as much as what it says
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// a data/ and config/ of its own under the temp dir, for the bot to be run in
+fn setup(name: &str, triggers: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cynobot-check-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("data")).unwrap();
+    fs::create_dir_all(dir.join("config")).unwrap();
+    fs::write(dir.join("data/passive_advice.list"), "advice\n").unwrap();
+    fs::write(dir.join("data/questions.list"), "question?\n").unwrap();
+    fs::write(dir.join("data/triggers.map"), triggers).unwrap();
+    fs::write(dir.join("data/commands_text.map"), "").unwrap();
+    fs::write(dir.join("config/commands.map"), "!help=COMMANDS\n").unwrap();
+    fs::write(dir.join("config/channels.list"), "chan\n").unwrap();
+    dir
+}
+
+fn check(dir: &PathBuf) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_cynobot")).arg("check").current_dir(dir).output().unwrap();
+    let _ = fs::remove_dir_all(dir);
+    (output.status.success(), String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[test]
+fn clean_files_exit_zero() {
+    let (success, stdout) = check(&setup("clean", "hi=hello {user}\n"));
+    assert!(success, "{}", stdout);
+    assert!(stdout.contains("0 errors, 0 warnings"), "{}", stdout);
+}
+
+#[test]
+fn warnings_alone_exit_zero() {
+    let (success, stdout) = check(&setup("warnings", "hi=hello\nhi=hey\n"));
+    assert!(success, "{}", stdout);
+    assert!(stdout.contains("0 errors, 1 warnings"), "{}", stdout);
+}
+
+#[test]
+fn errors_exit_non_zero() {
+    let (success, stdout) = check(&setup("errors", "hi=[nope\n"));
+    assert!(!success, "{}", stdout);
+    assert!(stdout.contains("data/triggers.map:1: error: unknown list 'nope'"), "{}", stdout);
+    assert!(stdout.contains("1 errors, 0 warnings"), "{}", stdout);
+}
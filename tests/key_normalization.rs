@@ -0,0 +1,214 @@
+// keys built from list entries used to keep their capitals, so they could never match a message,
+// which is always lowercased before it's compared
+use cynobot::backend::{ ChatBackend, ChatEvent, ChatMessage };
+use cynobot::bot::handle_message;
+use cynobot::data::ListsMaps;
+use cynobot::env::Env;
+use cynobot::state::{ MinMax, State };
+use std::time::{ Duration, UNIX_EPOCH };
+
+const TRIGGERS: &str = "\
+[greetings=hi back
+Rúst=the best
+{me} Bot=busted
+/I like (Java)/=why {1}
+hey @{me}=hello @{user}
+/shrug=meh
+";
+
+// remembers what the bot says instead of sending it anywhere
+#[derive(Default)]
+struct RecordingBackend {
+    sent: Vec<String>,
+}
+
+impl ChatBackend for RecordingBackend {
+    async fn next_event(&mut self) -> anyhow::Result<ChatEvent> {
+        Ok(ChatEvent::Quit)
+    }
+
+    async fn send_message(&mut self, _channel: &str, msg: &str) -> anyhow::Result<()> {
+        self.sent.push(String::from(msg));
+        Ok(())
+    }
+
+    async fn join(&mut self, _channel: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn part(&mut self, _channel: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+fn load(triggers: &str) -> ListsMaps {
+    let lists = vec![
+        (String::from("passive_advice"), String::from("advice\n")),
+        (String::from("questions"), String::from("question?\n")),
+        (String::from("greetings"), String::from("HeyGuys\nHello\nGOOD Morning\n")),
+        (String::from("moms"), String::from("mom\nmum\nyour mother\n")),
+    ];
+    ListsMaps::parse(&lists, triggers, "", "!config=CONFIG\n").unwrap()
+}
+
+async fn responses(messages: &[&str]) -> Vec<String> {
+    responses_to(TRIGGERS, messages).await
+}
+
+async fn responses_to(triggers: &str, messages: &[&str]) -> Vec<String> {
+    let messages: Vec<(bool, &str)> = messages.iter().map(|text| (false, *text)).collect();
+    responses_from(triggers, &messages).await
+}
+
+// what the bot answers to each message, sent by a mod or not, with cooldowns off so every message
+// can get an answer
+async fn responses_from(triggers: &str, messages: &[(bool, &str)]) -> Vec<String> {
+    let mut lm = load(triggers);
+    let (mut env, _clock) = Env::manual(0);
+    let mut state = State::new(vec!["chan"], &env);
+    state.ignore_cooldowns = true;
+    let mut backend = RecordingBackend::default();
+
+    for (is_moderator, text) in messages {
+        handle_message(&mut state, &mut lm, &mut env, &mut backend, message(*is_moderator, text)).await;
+    }
+    backend.sent
+}
+
+fn message(is_moderator: bool, text: &str) -> ChatMessage {
+    ChatMessage {
+        channel: String::from("chan"),
+        user: String::from("viewer"),
+        text: String::from(text),
+        is_broadcaster: false,
+        is_moderator,
+        emotes: Vec::new(),
+    }
+}
+
+#[test]
+fn keys_are_normalized_when_loaded() {
+    let lm = load(TRIGGERS);
+    for key in ["heyguys", "hello", "rust"] {
+        assert!(lm.triggers.contains_key(key), "missing key '{}'", key);
+    }
+    assert!(!lm.triggers.contains_key("HeyGuys"));
+
+    let multi: Vec<String> = lm.multi_triggers.iter().map(|multi| multi.triggers.join(" ")).collect();
+    assert!(multi.contains(&String::from("good morning")), "{:?}", multi);
+    assert!(multi.contains(&String::from("somewhatinaccurate bot")), "{:?}", multi);
+}
+
+#[tokio::test]
+async fn mixed_case_list_entries_match() {
+    let sent = responses(&["HeyGuys", "heyguys", "good MORNING everyone", "hello!"]).await;
+    assert_eq!(sent, ["hi back", "hi back", "hi back", "hi back"]);
+}
+
+#[tokio::test]
+async fn literal_and_me_keys_match() {
+    let sent = responses(&["RUST", "rust", "are you a SomewhatInaccurate bot"]).await;
+    assert_eq!(sent, ["the best", "the best", "busted"]);
+}
+
+#[tokio::test]
+async fn regex_keys_ignore_case_and_keep_the_captured_casing() {
+    let sent = responses(&["i like JAVA", "I LIKE java"]).await;
+    assert_eq!(sent, ["why JAVA", "why java"]);
+}
+
+#[tokio::test]
+async fn at_words_that_arent_options_stay_in_the_key() {
+    let sent = responses(&["hey @somewhatinaccurate"]).await;
+    assert_eq!(sent, ["hello @viewer"]);
+}
+
+#[tokio::test]
+async fn a_key_starting_with_a_slash_isnt_always_a_regex() {
+    let sent = responses(&["/shrug"]).await;
+    assert_eq!(sent, ["meh"]);
+}
+
+// which of the triggers in a message answers
+const ORDERING: &str = "\
+rust=single
+learn rust=multi
+go @priority:1=priority
+java=java
+python @fuzzy=fuzzy
+lucky @chance:5%=lucky
+never @priority:5 @chance:0%=never
+";
+
+#[tokio::test]
+async fn priority_then_words_then_exact_matches_go_first() {
+    // more words beat fewer, wherever they are in the message
+    assert_eq!(responses_to(ORDERING, &["rust or learn rust"]).await, ["multi"]);
+    // a higher priority beats more words
+    assert_eq!(responses_to(ORDERING, &["learn rust go"]).await, ["priority"]);
+    // no typos beats a typo, even later in the message
+    assert_eq!(responses_to(ORDERING, &["pyhton java"]).await, ["java"]);
+    assert_eq!(responses_to(ORDERING, &["python java"]).await, ["fuzzy"]);
+    // otherwise the first in the message
+    assert_eq!(responses_to(ORDERING, &["java rust"]).await, ["java"]);
+    assert_eq!(responses_to(ORDERING, &["rust java"]).await, ["single"]);
+}
+
+#[tokio::test]
+async fn one_answer_per_message() {
+    // MAX_RESPONSES is 1, and the channel's cooldown would hold back a second answer anyway
+    assert_eq!(responses_to(ORDERING, &["rust java go pyhton learn rust"]).await, ["priority"]);
+    // one that doesn't roll its chance makes way for the next
+    assert_eq!(responses_to(ORDERING, &["never java"]).await, ["java"]);
+}
+
+#[tokio::test]
+async fn a_repeated_key_is_one_candidate() {
+    // said 200 times it would get 200 rolls at its 5% and almost surely answer, it only gets one
+    let text = vec!["lucky"; 200].join(" ");
+    assert!(responses_to(ORDERING, &[&text]).await.is_empty());
+    assert_eq!(responses_to(ORDERING, &["rust rust rust"]).await, ["single"]);
+}
+
+#[tokio::test]
+async fn only_mods_change_chances() {
+    let sent = responses_from(TRIGGERS, &[(false, "!config chance 0"), (false, "!config chance rust 0"), (false, "rust")]).await;
+    assert_eq!(sent, ["the best"]);
+}
+
+#[tokio::test]
+async fn chances_are_set_for_the_key_as_written() {
+    let sent = responses_from(TRIGGERS, &[
+        (true, "!config chance Rúst 0"),
+        (true, "!config CHANCE /I like (Java)/ 0"),
+        (true, "!config chance {me} Bot 0"),
+        (false, "rust"),
+        (false, "i like java"),
+        (false, "a somewhatinaccurate bot"),
+        (false, "/shrug"),
+    ]).await;
+    assert_eq!(sent, [
+        "successfully changed the chance of answering 'rust' to 0%",
+        "successfully changed the chance of answering 'I like (Java)' to 0%",
+        "successfully changed the chance of answering 'somewhatinaccurate bot' to 0%",
+        "meh",
+    ]);
+}
+
+#[tokio::test]
+async fn keys_from_one_list_line_share_a_cooldown() {
+    let mut lm = load("[moms @cooldown:5m=your mom\nrust @cooldown:5m=the best\n");
+    let (mut env, clock) = Env::manual(0);
+    let mut state = State::new(vec!["chan"], &env);
+    // only the triggers' cooldowns, the channel can answer every message
+    for cstate in state.channels.values_mut() {
+        cstate.next_message = MinMax::new(Duration::ZERO, Duration::ZERO);
+    }
+    let mut backend = RecordingBackend::default();
+
+    for (secs, text) in [(1, "mom"), (2, "mum"), (3, "your mother"), (4, "rust"), (302, "mum"), (303, "mom")] {
+        clock.set(UNIX_EPOCH + Duration::from_secs(secs));
+        handle_message(&mut state, &mut lm, &mut env, &mut backend, message(false, text)).await;
+    }
+    assert_eq!(backend.sent, ["your mom", "the best", "your mom"]);
+}
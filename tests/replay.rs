@@ -0,0 +1,72 @@
+// `cynobot --replay` is for diffing transcripts, so the same log and seed has to give the same transcript
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// a message every 1000s, so the channel's own 600-800s cooldown between messages has always run out
+const LOG: &str = "\
+- a comment, then a blank line
+
+1700000000\t#Chan\talice\thi
+1700001000\tchan\tbob\thi
+1700002000\tchan\tcarol\ttell me a joke
+1700003000\tchan\talice\ttell me a joke
+1700006000\tchan\tbob\ttell me a joke
+1700007000\tchan\tCarol\thi
+";
+
+// a data/ and config/ of its own under the temp dir, with lists big enough that the seed matters
+fn setup(name: &str, log: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cynobot-replay-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("data")).unwrap();
+    fs::create_dir_all(dir.join("config")).unwrap();
+    fs::write(dir.join("data/passive_advice.list"), "advice\n").unwrap();
+    fs::write(dir.join("data/questions.list"), "question?\n").unwrap();
+    fs::write(dir.join("data/greetings.list"), "hello\nhey\nhowdy\nhiya\nyo\nsup\ngreetings\nwelcome\n").unwrap();
+    fs::write(dir.join("data/jokes.list"), "joke one\njoke two\njoke three\njoke four\njoke five\njoke six\n").unwrap();
+    fs::write(dir.join("data/triggers.map"), "hi=[greetings\njoke @cooldown:1h=[jokes\n").unwrap();
+    fs::write(dir.join("data/commands_text.map"), "").unwrap();
+    fs::write(dir.join("config/commands.map"), "!help=COMMANDS\n").unwrap();
+    fs::write(dir.join("config/channels.list"), "chan\n").unwrap();
+    fs::write(dir.join("chat.log"), log).unwrap();
+    dir
+}
+
+fn replay(dir: &PathBuf, seed: u64, transcript: &str) -> (bool, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_cynobot"))
+        .args(["--replay", "chat.log", "--transcript", transcript, "--seed", &seed.to_string()])
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    let transcript = fs::read_to_string(dir.join(transcript)).unwrap_or_default();
+    (output.status.success(), transcript, String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[test]
+fn same_seed_same_transcript() {
+    let dir = setup("seed", LOG);
+    let (success, first, stdout) = replay(&dir, 7, "first.txt");
+    assert!(success, "{}", stdout);
+    let (success, second, stdout) = replay(&dir, 7, "second.txt");
+    assert!(success, "{}", stdout);
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(first, second);
+    let lines: Vec<&str> = first.lines().collect();
+    // nothing for the message that opens the channel, and the joke's hour long cooldown skips the second one
+    assert_eq!(lines.len(), 4, "{}", first);
+    assert!(lines[0].starts_with("1700001000.000\tchan\t"), "{}", first);
+    assert!(lines[1].starts_with("1700002000.000\tchan\tjoke "), "{}", first);
+    assert!(lines[2].starts_with("1700006000.000\tchan\tjoke "), "{}", first);
+    assert!(lines[3].starts_with("1700007000.000\tchan\t"), "{}", first);
+}
+
+#[test]
+fn bad_log_exits_non_zero() {
+    let dir = setup("bad", "1700000000\tchan\talice\thi\n1700000001\tchan hi\n");
+    let (success, _, stdout) = replay(&dir, 7, "transcript.txt");
+    let _ = fs::remove_dir_all(&dir);
+    assert!(!success, "{}", stdout);
+    assert!(stdout.contains("line 2:"), "{}", stdout);
+}